@@ -0,0 +1,221 @@
+//! Whole-machine snapshot, rewind, and record/replay, built directly on top of
+//! [`space_time::allocator::Allocator`]: since every device's mutable state already lives behind
+//! an [`Allocator`] handle rather than in the device struct itself (see
+//! [`super::system_bus::SystemBus`]), an allocator-level snapshot captures almost the entire
+//! machine's state in one shot; the bus's central RNG stream and
+//! [`StoreBufferedMemory`](crate::core::store_buffer::StoreBufferedMemory)'s per-hart buffer
+//! bookkeeping are the two exceptions, held as plain struct state instead, and are captured
+//! alongside it.
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use space_time::allocator::Allocator;
+
+use crate::core::store_buffer::StoreBufferState;
+
+use super::system_bus::DeviceId;
+
+/// A point-in-time snapshot of a [`super::system_bus::SystemBus`]'s machine state: the backing
+/// allocator's own snapshot, the bus's central RNG stream, and every hart's store-buffer state
+/// (see [`StoreBufferState`]), tagged with the step counter (see
+/// [`super::system_bus::SystemBus::step`]) at which it was taken.
+pub struct Checkpoint<A: Allocator> {
+    pub(super) step: u64,
+    pub(super) allocator: A::Snapshot,
+    pub(super) rng: StdRng,
+    pub(super) store_buffer: StoreBufferState,
+}
+
+impl<A: Allocator> std::fmt::Debug for Checkpoint<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint").field("step", &self.step).finish_non_exhaustive()
+    }
+}
+
+/// Ring buffer of the most recently taken [`Checkpoint`]s, bounded so that rewinding stays bounded
+/// in memory regardless of how long a session runs.
+pub struct CheckpointRing<A: Allocator> {
+    checkpoints: VecDeque<Checkpoint<A>>,
+    capacity: usize,
+}
+
+impl<A: Allocator> std::fmt::Debug for CheckpointRing<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckpointRing")
+            .field("len", &self.checkpoints.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<A: Allocator> CheckpointRing<A> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a checkpoint ring needs at least one slot");
+        CheckpointRing {
+            checkpoints: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Pushes a new checkpoint, evicting the oldest one first if the ring is full.
+    pub fn push(&mut self, checkpoint: Checkpoint<A>) {
+        if self.checkpoints.len() == self.capacity {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(checkpoint);
+    }
+
+    /// Pops the most recent checkpoint, stepping one checkpoint back in time.
+    pub fn pop(&mut self) -> Option<Checkpoint<A>> {
+        self.checkpoints.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+}
+
+/// One externally-supplied event captured by a [`Recording`], keyed by the step counter at which
+/// it occurred, so a session can later be replayed deterministically from any checkpoint forward
+/// without needing the real external source (host stdin, a real timer, ...) again.
+///
+/// Notably absent: PLIC interrupt raises/lowers delivered through
+/// [`super::system_bus::SystemBus::get_plic_irq_callback`]. Those are always a deterministic
+/// consequence of machine state plus whatever external event caused them (a UART byte arriving, a
+/// block read completing, ...), which *is* captured here, so replaying the causing event already
+/// reproduces the same raise/lower without needing its own entry.
+#[derive(Debug, Clone)]
+pub enum RecordedEvent {
+    /// A byte delivered to a UART's RX FIFO from outside the simulated machine.
+    UartRx { step: u64, byte: u8 },
+    /// A `u64` drawn from the bus's central RNG stream (see
+    /// [`super::system_bus::SystemBus::rng_draw_u64`]).
+    RngDraw { step: u64, value: u64 },
+    /// Bytes read from a host-backed device's external byte stream (e.g. a sector read from a
+    /// [`crate::resources::block::Block`]'s backing file) rather than from allocator-covered
+    /// state, recorded so a replay doesn't need that host resource (the file, the socket, ...) to
+    /// still exist or still hold the same contents.
+    HostRead {
+        step: u64,
+        device: DeviceId,
+        bytes: Box<[u8]>,
+    },
+}
+
+/// An in-progress recording of external input and RNG draws, started by
+/// [`super::system_bus::SystemBus::start_recording`].
+#[derive(Debug, Default, Clone)]
+pub struct Recording {
+    events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    pub(super) fn record(&mut self, event: RecordedEvent) {
+        self.events.push(event);
+    }
+
+    /// Turns a finished recording into a [`Replay`] that feeds the same events back in order.
+    pub fn into_replay(self) -> Replay {
+        Replay {
+            events: self.events.into(),
+        }
+    }
+}
+
+/// Feeds back the external events captured by a [`Recording`], in order, so a session can be
+/// replayed deterministically forward from the checkpoint the recording started at.
+#[derive(Debug, Default, Clone)]
+pub struct Replay {
+    events: VecDeque<RecordedEvent>,
+}
+
+impl Replay {
+    /// Returns the next recorded UART RX byte, if the next event in the log is one delivered at
+    /// or before `step`, consuming it.
+    pub fn next_uart_rx(&mut self, step: u64) -> Option<u8> {
+        match self.events.front() {
+            Some(&RecordedEvent::UartRx { step: at, byte }) if at <= step => {
+                self.events.pop_front();
+                Some(byte)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the next recorded RNG draw, if due by `step`, consuming it.
+    pub fn next_rng_draw(&mut self, step: u64) -> Option<u64> {
+        match self.events.front() {
+            Some(&RecordedEvent::RngDraw { step: at, value }) if at <= step => {
+                self.events.pop_front();
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the next recorded host-read byte stream for `device`, if due by `step`, consuming
+    /// it.
+    pub fn next_host_read(&mut self, step: u64, device: DeviceId) -> Option<Box<[u8]>> {
+        match self.events.front() {
+            Some(RecordedEvent::HostRead {
+                step: at,
+                device: for_device,
+                ..
+            }) if *at <= step && *for_device == device => {
+                let Some(RecordedEvent::HostRead { bytes, .. }) = self.events.pop_front() else {
+                    unreachable!()
+                };
+                Some(bytes)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_events_replay_in_the_order_they_were_recorded() {
+        let mut recording = Recording::default();
+        recording.record(RecordedEvent::UartRx { step: 0, byte: b'a' });
+        recording.record(RecordedEvent::UartRx { step: 1, byte: b'b' });
+
+        let mut replay = recording.into_replay();
+        assert_eq!(replay.next_uart_rx(10), Some(b'a'));
+        assert_eq!(replay.next_uart_rx(10), Some(b'b'));
+        assert_eq!(replay.next_uart_rx(10), None);
+        assert!(replay.is_empty());
+    }
+
+    #[test]
+    fn an_event_is_not_due_before_its_recorded_step() {
+        let mut recording = Recording::default();
+        recording.record(RecordedEvent::UartRx { step: 5, byte: b'x' });
+
+        let mut replay = recording.into_replay();
+        assert_eq!(replay.next_uart_rx(4), None);
+        assert_eq!(replay.next_uart_rx(5), Some(b'x'));
+    }
+
+    #[test]
+    fn polling_the_wrong_event_kind_does_not_consume_it() {
+        let mut recording = Recording::default();
+        recording.record(RecordedEvent::RngDraw { step: 0, value: 42 });
+
+        let mut replay = recording.into_replay();
+        assert_eq!(replay.next_uart_rx(0), None);
+        // Still there for the right poll, since the mismatched poll above didn't consume it.
+        assert_eq!(replay.next_rng_draw(0), Some(42));
+    }
+}
@@ -1,54 +1,195 @@
+use std::cell::{Cell, RefCell, RefMut};
+use std::fmt;
+use std::ops::RangeInclusive;
 use std::rc::Weak;
 
-use super::PowerDown;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
 use crate::address_map::TwoWayAddressMap;
+use crate::board::checkpoint::{Checkpoint, CheckpointRing, RecordedEvent, Recording, Replay};
 use crate::bus::Bus;
-use crate::core::clint::Clint;
+use crate::core::scheduler::Scheduler;
+use crate::core::store_buffer::{HartId, StoreBufferedMemory};
 use crate::interrupt::{DynIrqCallback, IrqCallback};
 use crate::resources::plic::Plic;
 use crate::resources::ram::Ram;
-use crate::resources::rom::Rom;
-use crate::resources::uart::Uart;
 use crate::system_bus::AccessType;
 use space_time::allocator::Allocator;
 
-/// Enum that uniquely identifies every device attached to a [`SystemBus`] (as a slave).
+/// Default capacity of the ring buffer a [`SystemBus`] keeps of recent [`Checkpoint`]s, i.e. how
+/// many checkpoints [`SystemBus::rewind`] can step back through before running out.
+const DEFAULT_CHECKPOINT_CAPACITY: usize = 64;
+
+/// Opaque handle to a device registered with a [`SystemBus`] (as a slave), returned by
+/// [`SystemBus::register_device`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-pub(super) enum Resource {
-    Mrom,
-    Clint,
-    Plic,
-    Uart0,
-    Flash,
-    Dram,
-    PowerDown,
+pub struct DeviceId(u32);
+
+/// Register width/alignment/access-type constraints that a registered device imposes on its
+/// slave interface.
+///
+/// Since a device registered through [`SystemBus::register_device`] is only known as a
+/// `dyn Bus<A>`, these constraints can't be encoded in the type system the way a statically-typed
+/// register file could; [`SystemBus::check_access`] checks them at runtime instead.
+#[derive(Copy, Clone)]
+pub struct AccessPolicy {
+    /// Required alignment of the *mapped* address (relative to the device's base), in bytes.
+    /// Must be a power of two.
+    pub alignment: u32,
+    /// Whether `size` (in bytes) is an accepted access width for this device.
+    pub accepted_sizes: fn(usize) -> bool,
+    /// Whether `access_type` is accepted at all, e.g. a ROM rejects [`AccessType::Write`].
+    pub accepted_access_types: fn(AccessType) -> bool,
+}
+
+impl AccessPolicy {
+    /// No constraints beyond fitting within the device's registered range: any size, any
+    /// alignment, any access type. This is the policy used by plain byte-addressable memories.
+    pub const UNRESTRICTED: Self = Self {
+        alignment: 1,
+        accepted_sizes: |_| true,
+        accepted_access_types: |_| true,
+    };
+
+    /// Read-only device: rejects [`AccessType::Write`], otherwise unrestricted. Used by e.g.
+    /// mask ROM and flash.
+    pub const READ_ONLY: Self = Self {
+        alignment: 1,
+        accepted_sizes: |_| true,
+        accepted_access_types: |access_type| !matches!(access_type, AccessType::Write),
+    };
+
+    /// Write-only, 4-byte-aligned, word-sized accesses only. Used by e.g. the power-down device.
+    pub const WRITE_ONLY_WORD: Self = Self {
+        alignment: 4,
+        accepted_sizes: |size| size == 4,
+        accepted_access_types: |access_type| matches!(access_type, AccessType::Write),
+    };
+
+    /// Word (4-byte) or double-word (8-byte) accesses only, 4-byte-aligned. Used by e.g. the
+    /// CLINT.
+    pub const WORD_OR_DOUBLE_WORD: Self = Self {
+        alignment: 4,
+        accepted_sizes: |size| size == 4 || size == 8,
+        accepted_access_types: |_| true,
+    };
+
+    /// Word (4-byte) accesses only, 4-byte-aligned. Used by e.g. the PLIC.
+    pub const WORD: Self = Self {
+        alignment: 4,
+        accepted_sizes: |size| size == 4,
+        accepted_access_types: |_| true,
+    };
+}
+
+/// A device registered with a [`SystemBus`], together with the [`AccessPolicy`] it was
+/// registered under.
+struct Slot<A: Allocator> {
+    device: Box<dyn Bus<A>>,
+    policy: AccessPolicy,
+}
+
+impl<A: Allocator> fmt::Debug for Slot<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for AccessPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessPolicy")
+            .field("alignment", &self.alignment)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Abstraction of a system's main bus connecting all devices to the core.
 ///
 /// This can be thought of as a (TileLink) crossbar providing a single *master* interface for the
-/// entire 32-bit physical address space, and delegating requests to the appropriate agent's *slave*
-/// interface depending on a configurable address mapping.
+/// entire 32-bit physical address space, and delegating requests to whichever *slave* a
+/// configurable address mapping points `address` at. Unlike a fixed set of slaves baked into the
+/// bus itself, slaves are attached dynamically through [`SystemBus::register_device`]: anyone
+/// holding a `&mut SystemBus` can plug in a `Box<dyn Bus<A>>`, from the board's default
+/// `mrom`/`clint`/`uart0` set to arbitrary external peripherals, each declaring its own
+/// [`AccessPolicy`].
 ///
 /// Note that vacant memory regions (i.e. unmapped address ranges) are allowed, but accessing them
 /// will do nothing.
 ///
 /// Accesses are always in the form of `(address, size)` pairs. The access request is forwarded to
 /// the *slave* interface that `address` maps to, if and only if the entire address range
-/// `address..(address+size)` is contained within the memory region that `address` is in. Otherwise,
-/// the access is not forwarded and will do nothing.
+/// `address..(address+size)` is contained within the memory region that `address` is in, and the
+/// device's [`AccessPolicy`] accepts it. Otherwise, the access is not forwarded and will do
+/// nothing.
 ///
 /// See also the [`crate::system_bus::SystemBus`] trait.
 #[derive(Debug)]
-pub(super) struct SystemBus<A: Allocator> {
-    pub memory_map: TwoWayAddressMap<Resource>,
-    pub mrom: Rom<A>,
-    pub clint: Clint<A>,
+pub(crate) struct SystemBus<A: Allocator> {
+    memory_map: TwoWayAddressMap<DeviceId>,
+    devices: Vec<Slot<A>>,
+    /// Kept as a typed handle (in addition to being registered as a slave) since
+    /// [`SystemBus::get_plic_irq_callback`] needs to raise/lower interrupt sources directly,
+    /// which isn't part of the [`Bus`] interface.
     pub plic: Plic<A>,
-    pub uart0: Uart<A>,
-    pub flash: Rom<A>,
-    pub dram: Ram<A>,
-    pub power_down: PowerDown<A>,
+    /// Main memory, shared by every hart but seen through a per-hart store buffer (see
+    /// [`StoreBufferedMemory`]) rather than through the [`Bus`] interface directly: a hart's
+    /// accesses need to be tagged with its [`HartId`], which `Bus::read`/`Bus::write` have no room
+    /// for, so this is reached through [`Self::read_dram`]/[`Self::write_dram`]/[`Self::fence_dram`]
+    /// instead of [`Self::register_device`].
+    dram: RefCell<StoreBufferedMemory<A>>,
+    /// Decides hart interleaving and store-buffer drain order, drawing from [`Self::rng`] so the
+    /// choices stay reproducible given the bus's seed. Per-hart CLINT `msip`/`mtimecmp` registers
+    /// now live in [`crate::resources::clint::Clint`] (a separate, independently-registered
+    /// device, like [`Plic`]). Per-context PLIC claim/complete, generalizing
+    /// [`Self::get_plic_irq_callback`]'s flat `1..=52` source space to multiple contexts, is a
+    /// known, *not yet implemented* gap: it requires changes inside `resources::plic` itself.
+    /// Flagging it explicitly here rather than quietly scoping it out; tracked as a follow-up.
+    scheduler: RefCell<Scheduler>,
+    /// The single, central source of nondeterminism for the whole machine: hart interleaving,
+    /// store-buffer drain order, UART RX timing, uninitialized-memory fill patterns, and anything
+    /// else that would otherwise reach for `rand::thread_rng` should draw from this instead, via
+    /// [`Self::rng`], so that an entire run is reproducible given the seed passed to
+    /// [`Self::new_seeded`].
+    rng: RefCell<StdRng>,
+    /// Monotonically increasing counter, advanced by [`Self::advance_step`], that ties together
+    /// checkpoints and recorded external events so a session can be replayed deterministically.
+    step: Cell<u64>,
+    checkpoints: RefCell<CheckpointRing<A>>,
+    recording: RefCell<Option<Recording>>,
+    /// An in-progress replay (see [`Self::start_replay`]), consulted by [`Self::rng_draw_u64`],
+    /// [`Self::poll_uart_rx`] and [`Self::poll_host_read`] so a session recorded from some
+    /// checkpoint can be fed the exact same external input again when restored to it.
+    replay: RefCell<Option<Replay>>,
+}
+
+/// Exposes [`SystemBus::read_dram_direct`]/[`SystemBus::write_dram_direct`] as an ordinary
+/// [`Bus`] slave, registered via [`SystemBus::register_dram`], so bus masters that aren't a hart
+/// (e.g. a DMA engine) can reach main memory the same way they reach any other device.
+struct DramSlave<A: Allocator> {
+    bus: Weak<SystemBus<A>>,
+}
+
+impl<A: Allocator> Bus<A> for DramSlave<A> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.read_dram_direct(allocator, buf, address);
+        }
+    }
+
+    fn read_debug(&self, _buf: &mut [u8], _allocator: &A, _address: u32) {
+        // `Ram` only exposes mutable-allocator reads (to support lazy zero-fill), so there's no
+        // side-effect-free way to peek at it; same as an unmapped address, a debug read here does
+        // nothing rather than returning a value.
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.write_dram_direct(allocator, address, buf);
+        }
+    }
 }
 
 struct PlicIrqCallback<A: Allocator> {
@@ -59,22 +200,350 @@ struct PlicIrqCallback<A: Allocator> {
 impl<A: Allocator> IrqCallback<A> for PlicIrqCallback<A> {
     fn raise(&self, allocator: &mut A) {
         if let Some(bus) = self.bus.upgrade() {
-            bus.plic.raise(allocator, self.index)
+            // Not itself recorded: it's a deterministic consequence of whatever external event
+            // (already recorded separately, e.g. a `UartRx`) caused it, so replaying that event
+            // reproduces this raise too. See the note on `RecordedEvent`.
+            bus.plic.raise(allocator, self.index);
         }
     }
 
     fn lower(&self, allocator: &mut A) {
         if let Some(bus) = self.bus.upgrade() {
-            bus.plic.lower(allocator, self.index)
+            bus.plic.lower(allocator, self.index);
         }
     }
 }
 
 impl<A: Allocator> SystemBus<A> {
-    /// Validates the `(address, size)` pair, returning `Some((resource, mapped_address))` if the
-    /// access is accepted, and `None` otherwise.
-    fn check_access(&self, address: u32, size: usize) -> Option<(Resource, u32)> {
-        let (range, Some(&resource)) = self.memory_map.range_value(address) else {
+    /// Creates a new, empty bus seeded from entropy: no devices are registered, so every address
+    /// is vacant. Since the seed isn't recorded anywhere, a bus created this way cannot be
+    /// bit-for-bit replayed; use [`Self::new_seeded`] for that.
+    ///
+    /// `plic` is kept as a typed field so that [`Self::get_plic_irq_callback`] can reach it
+    /// directly; register it as a slave too via [`Self::register_device`] if it should also be
+    /// addressable.
+    pub fn new(plic: Plic<A>, dram: Ram<A>, harts: Vec<HartId>) -> Self {
+        Self::new_seeded(plic, dram, harts, rand::random())
+    }
+
+    /// Like [`Self::new`], but seeds the bus's central RNG stream (see [`Self::rng`]) from `seed`
+    /// instead of from entropy. Two buses created with the same seed, registered with the same
+    /// devices and driven with the same external inputs, will behave bit-for-bit identically.
+    pub fn new_seeded(plic: Plic<A>, dram: Ram<A>, harts: Vec<HartId>, seed: u64) -> Self {
+        SystemBus {
+            memory_map: TwoWayAddressMap::new(),
+            devices: Vec::new(),
+            plic,
+            dram: RefCell::new(StoreBufferedMemory::new(dram)),
+            scheduler: RefCell::new(Scheduler::new(harts)),
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+            step: Cell::new(0),
+            checkpoints: RefCell::new(CheckpointRing::new(DEFAULT_CHECKPOINT_CAPACITY)),
+            recording: RefCell::new(None),
+            replay: RefCell::new(None),
+        }
+    }
+
+    /// Reads `buf.len()` bytes of main memory at `address` as observed by `hart`, through its
+    /// store buffer (see [`StoreBufferedMemory::read`]).
+    pub fn read_dram(&self, hart: HartId, allocator: &mut A, buf: &mut [u8], address: u32) {
+        self.dram.borrow_mut().read(hart, allocator, buf, address);
+    }
+
+    /// Buffers a store to main memory from `hart` (see [`StoreBufferedMemory::write`]); visible to
+    /// `hart`'s own subsequent [`Self::read_dram`] calls immediately, to every other hart only once
+    /// drained.
+    pub fn write_dram(&self, hart: HartId, address: u32, buf: &[u8]) {
+        self.dram.borrow_mut().write(hart, address, buf);
+    }
+
+    /// Fully drains `hart`'s store buffer to main memory, as required by `FENCE`, `FENCE.TSO`, and
+    /// any AMO or `.aq`/`.rl` access.
+    pub fn fence_dram(&self, hart: HartId, allocator: &mut A) {
+        self.dram.borrow_mut().fence(hart, allocator);
+    }
+
+    /// Reads `buf.len()` bytes of main memory at `address` directly, bypassing every hart's store
+    /// buffer (see [`StoreBufferedMemory::read_direct`]). For bus masters that aren't a hart (e.g.
+    /// [`crate::resources::dma::Dma`], through [`Self::register_dram`]) and so have no buffer of
+    /// their own to forward from.
+    pub fn read_dram_direct(&self, allocator: &mut A, buf: &mut [u8], address: u32) {
+        self.dram.borrow_mut().read_direct(allocator, buf, address);
+    }
+
+    /// Writes directly to main memory, bypassing every hart's store buffer; see
+    /// [`Self::read_dram_direct`]/[`StoreBufferedMemory::write_direct`].
+    pub fn write_dram_direct(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        self.dram.borrow_mut().write_direct(allocator, address, buf);
+    }
+
+    /// Picks the hart that should execute next and drains a nondeterministic slice of its store
+    /// buffer, both drawn from [`Self::rng`] through [`Scheduler`] so the choice is reproducible
+    /// given the bus's seed.
+    pub fn schedule_step(&self, allocator: &mut A) -> HartId {
+        let hart = self.scheduler.borrow().pick_hart(&mut *self.rng.borrow_mut());
+        let pending = self.dram.borrow().pending(hart);
+        let drain_count = self
+            .scheduler
+            .borrow()
+            .pick_drain_count(&mut *self.rng.borrow_mut(), pending);
+        self.dram.borrow_mut().drain(hart, allocator, drain_count);
+        hart
+    }
+
+    /// Borrows the bus's central RNG stream. Everything in the emulator that needs randomness
+    /// should draw from this rather than `rand::thread_rng`, so a whole run stays reproducible
+    /// given the seed passed to [`Self::new_seeded`].
+    pub fn rng(&self) -> RefMut<'_, StdRng> {
+        self.rng.borrow_mut()
+    }
+
+    /// Reads back the current state of the central RNG stream, e.g. to save alongside a
+    /// checkpoint so a session can later be resumed exactly where it left off.
+    pub fn rng_state(&self) -> StdRng {
+        self.rng.borrow().clone()
+    }
+
+    /// Draws a `u64`, preferring a value due from an in-progress replay (see
+    /// [`Self::start_replay`]) over the central RNG stream, so a replayed session sees the exact
+    /// same draws as the recording it's replaying rather than diverging from a fresh draw. A
+    /// freshly drawn value is recorded (keyed by the current step) if a recording is in progress.
+    ///
+    /// [`Self::rng`] has other direct consumers besides this one (namely [`Self::schedule_step`],
+    /// through [`Scheduler`]), so returning a replayed value here without also advancing
+    /// [`Self::rng`] would leave it at a different position than it held during the original
+    /// recording, desynchronizing every later draw those other consumers make. To keep the whole
+    /// stream in lockstep with the recording, a replayed draw still consumes exactly one `u64`
+    /// from [`Self::rng`], discarding it.
+    pub fn rng_draw_u64(&self) -> u64 {
+        let step = self.step.get();
+        if let Some(value) = self
+            .replay
+            .borrow_mut()
+            .as_mut()
+            .and_then(|replay| replay.next_rng_draw(step))
+        {
+            self.rng.borrow_mut().next_u64();
+            return value;
+        }
+
+        let value = self.rng.borrow_mut().next_u64();
+        self.record(RecordedEvent::RngDraw { step, value });
+        value
+    }
+
+    /// The current step counter, advanced by [`Self::advance_step`] and used to key recorded
+    /// external events and checkpoints to a point in the run.
+    pub fn step(&self) -> u64 {
+        self.step.get()
+    }
+
+    /// Advances the step counter by one, returning the value it held before advancing. Should be
+    /// called once per unit of simulated progress (e.g. once per retired instruction, or once per
+    /// scheduler turn) that external events and checkpoints should be able to distinguish.
+    pub fn advance_step(&self) -> u64 {
+        let step = self.step.get();
+        self.step.set(step + 1);
+        step
+    }
+
+    /// Takes a [`Checkpoint`] of the current machine state (everything behind `allocator`, plus
+    /// the RNG stream and every hart's store-buffer state, see
+    /// [`StoreBufferedMemory::state`]), without recording it into the rewind ring. Use this to
+    /// hand a checkpoint to a front-end that wants to hold onto a specific point in time (e.g. the
+    /// point a recording started at) and [`Self::restore`] it explicitly later; use
+    /// [`Self::checkpoint`] instead for the rolling rewind history [`Self::rewind`] steps back
+    /// through.
+    pub fn snapshot(&self, allocator: &A) -> Checkpoint<A> {
+        Checkpoint {
+            step: self.step.get(),
+            allocator: allocator.snapshot(),
+            rng: self.rng_state(),
+            store_buffer: self.dram.borrow().state(),
+        }
+    }
+
+    /// Restores the machine to an arbitrary, previously taken [`Checkpoint`] (e.g. one returned by
+    /// [`Self::snapshot`]), without touching the rewind ring. Rolls back every hart's store-buffer
+    /// state (see [`StoreBufferedMemory::restore_state`]) in lockstep with `allocator`, so a hart's
+    /// still-undrained stores at checkpoint time don't outlive the rollback and later drain into
+    /// what's supposed to be a past `dram`.
+    pub fn restore(&self, allocator: &mut A, checkpoint: &Checkpoint<A>) {
+        allocator.restore(&checkpoint.allocator);
+        *self.rng.borrow_mut() = checkpoint.rng.clone();
+        self.step.set(checkpoint.step);
+        self.dram
+            .borrow_mut()
+            .restore_state(checkpoint.store_buffer.clone());
+    }
+
+    /// Takes a [`Checkpoint`] of the current machine state and pushes it onto the ring buffer,
+    /// evicting the oldest one if it's full.
+    pub fn checkpoint(&self, allocator: &A) {
+        let checkpoint = self.snapshot(allocator);
+        self.checkpoints.borrow_mut().push(checkpoint);
+    }
+
+    /// Restores the machine to the most recently pushed [`Checkpoint`], stepping one checkpoint
+    /// back in time. Returns `false` (leaving the machine untouched) if no checkpoint is
+    /// available.
+    pub fn rewind(&self, allocator: &mut A) -> bool {
+        let Some(checkpoint) = self.checkpoints.borrow_mut().pop() else {
+            return false;
+        };
+        self.restore(allocator, &checkpoint);
+        true
+    }
+
+    /// Starts recording external input (UART RX bytes, host-backed device reads) and RNG draws,
+    /// keyed by step, discarding any prior in-progress recording. PLIC interrupt raises/lowers
+    /// delivered via [`Self::get_plic_irq_callback`] aren't recorded separately; see the note on
+    /// [`RecordedEvent`].
+    pub fn start_recording(&self) {
+        *self.recording.borrow_mut() = Some(Recording::default());
+    }
+
+    /// Stops the in-progress recording (if any) and returns it as a [`Replay`], ready to feed the
+    /// same events back into a machine restored to the checkpoint the recording started at.
+    pub fn stop_recording(&self) -> Option<Replay> {
+        self.recording
+            .borrow_mut()
+            .take()
+            .map(Recording::into_replay)
+    }
+
+    fn record(&self, event: RecordedEvent) {
+        if let Some(recording) = self.recording.borrow_mut().as_mut() {
+            recording.record(event);
+        }
+    }
+
+    /// Starts replaying a [`Replay`] (e.g. one obtained from [`Self::stop_recording`]) against the
+    /// machine from its current state, discarding any prior in-progress replay. The machine should
+    /// first be [`Self::restore`]d to the checkpoint the recording started at.
+    pub fn start_replay(&self, replay: Replay) {
+        *self.replay.borrow_mut() = Some(replay);
+    }
+
+    /// Whether a replay started with [`Self::start_replay`] is still in progress.
+    pub fn is_replaying(&self) -> bool {
+        self.replay.borrow().is_some()
+    }
+
+    /// Returns the UART RX byte due to be delivered at the current step, if a replay is in
+    /// progress and one is queued. Host-backed UART backends (see
+    /// [`crate::resources::uart_io`]) should call this first and only poll their live source if it
+    /// returns `None`, so a replay sees the exact same RX bytes the recording captured rather than
+    /// whatever the host source (now potentially absent or different) would produce.
+    pub fn poll_uart_rx(&self) -> Option<u8> {
+        self.replay
+            .borrow_mut()
+            .as_mut()
+            .and_then(|replay| replay.next_uart_rx(self.step.get()))
+    }
+
+    /// Returns the next recorded external byte stream read due for `device` at the current step,
+    /// if a replay is in progress and one is queued. Host-backed devices (e.g.
+    /// [`crate::resources::block::Block`]) should call this first and only read their backing
+    /// host resource if it returns `None`.
+    pub fn poll_host_read(&self, device: DeviceId) -> Option<Box<[u8]>> {
+        self.replay
+            .borrow_mut()
+            .as_mut()
+            .and_then(|replay| replay.next_host_read(self.step.get(), device))
+    }
+
+    /// Records a UART RX byte delivered from outside the simulated machine, if a recording is in
+    /// progress. Host-backed UART backends (see [`crate::resources::uart_io`]) should call this
+    /// alongside actually feeding the byte to the RX FIFO.
+    pub fn record_uart_rx(&self, byte: u8) {
+        self.record(RecordedEvent::UartRx {
+            step: self.step.get(),
+            byte,
+        });
+    }
+
+    /// Records bytes read from a host-backed device's external byte stream (e.g. a sector read
+    /// from a [`crate::resources::block::Block`]'s backing file), if a recording is in progress.
+    /// `device` is the [`DeviceId`] returned when the device was registered, used to key the
+    /// event back to the right device on replay.
+    pub fn record_host_read(&self, device: DeviceId, bytes: &[u8]) {
+        self.record(RecordedEvent::HostRead {
+            step: self.step.get(),
+            device,
+            bytes: bytes.into(),
+        });
+    }
+
+    /// Registers `device` as a slave occupying `range`, to be validated against `policy` on every
+    /// access. Returns a [`DeviceId`] handle uniquely identifying this registration.
+    ///
+    /// # Panics
+    /// Panics if `range` overlaps the range of an already-registered device.
+    pub fn register_device(
+        &mut self,
+        range: RangeInclusive<u32>,
+        device: Box<dyn Bus<A>>,
+        policy: AccessPolicy,
+    ) -> DeviceId {
+        let id = DeviceId(self.devices.len() as u32);
+        self.memory_map
+            .insert(range, id)
+            .expect("device range overlaps an already-registered device");
+        self.devices.push(Slot { device, policy });
+        id
+    }
+
+    /// Like [`Self::register_device`], but for a device that needs to know its own [`DeviceId`]
+    /// (e.g. to pass to [`Self::record_host_read`]) before it can be built, the same
+    /// chicken-and-egg problem `Rc::new_cyclic` solves for self-referential `Rc`s: `build` is
+    /// handed the `DeviceId` the device is about to be registered under, and returns the device to
+    /// actually register.
+    ///
+    /// # Panics
+    /// Panics if `range` overlaps the range of an already-registered device.
+    pub fn register_device_with(
+        &mut self,
+        range: RangeInclusive<u32>,
+        policy: AccessPolicy,
+        build: impl FnOnce(DeviceId) -> Box<dyn Bus<A>>,
+    ) -> DeviceId {
+        let id = DeviceId(self.devices.len() as u32);
+        self.memory_map
+            .insert(range, id)
+            .expect("device range overlaps an already-registered device");
+        self.devices.push(Slot {
+            device: build(id),
+            policy,
+        });
+        id
+    }
+
+    /// Registers main memory itself as a slave occupying `range`, so bus masters that aren't a
+    /// hart (and so have no [`HartId`] of their own to buffer through, e.g.
+    /// [`crate::resources::dma::Dma`]) can reach it through the ordinary
+    /// [`Bus::read`]/[`Bus::write`] path like any other device, via
+    /// [`Self::read_dram_direct`]/[`Self::write_dram_direct`]. `bus` is a weak handle back to this
+    /// same bus, mirroring the pattern [`crate::resources::dma::Dma`] and other self-referencing
+    /// devices use. Harts keep going through [`Self::read_dram`]/[`Self::write_dram`] directly
+    /// instead, which is unaffected by whether this is called.
+    ///
+    /// # Panics
+    /// Panics if `range` overlaps the range of an already-registered device.
+    pub fn register_dram(
+        &mut self,
+        range: RangeInclusive<u32>,
+        policy: AccessPolicy,
+        bus: Weak<Self>,
+    ) -> DeviceId {
+        self.register_device(range, Box::new(DramSlave { bus }), policy)
+    }
+
+    /// Validates the `(address, size)` pair against the registered memory map and the owning
+    /// device's [`AccessPolicy`] (size and alignment only, *not* access type), returning
+    /// `Some((id, mapped_address))` if the access is accepted, and `None` otherwise.
+    fn check_access(&self, address: u32, size: usize) -> Option<(DeviceId, u32)> {
+        let (range, Some(&id)) = self.memory_map.range_value(address) else {
             return None;
         };
 
@@ -87,19 +556,17 @@ impl<A: Allocator> SystemBus<A> {
             return None;
         }
 
-        Some((resource, address - range.start()))
+        let mapped_address = address - range.start();
+        let policy = &self.devices[id.0 as usize].policy;
+        if mapped_address % policy.alignment != 0 || !(policy.accepted_sizes)(size) {
+            return None;
+        }
+
+        Some((id, mapped_address))
     }
 
-    fn bus_of(&self, resource: Resource) -> &dyn Bus<A> {
-        match resource {
-            Resource::Mrom => &self.mrom,
-            Resource::Clint => &self.clint,
-            Resource::Plic => &self.plic,
-            Resource::Uart0 => &self.uart0,
-            Resource::Flash => &self.flash,
-            Resource::Dram => &self.dram,
-            Resource::PowerDown => &self.power_down,
-        }
+    fn device(&self, id: DeviceId) -> &dyn Bus<A> {
+        self.devices[id.0 as usize].device.as_ref()
     }
 
     /// Panics if `index` is not in 1..=52
@@ -110,55 +577,83 @@ impl<A: Allocator> SystemBus<A> {
 
         DynIrqCallback(Box::new(PlicIrqCallback { bus, index }))
     }
-
-    pub(super) fn drop(self, allocator: &mut A) {
-        self.mrom.drop(allocator);
-        self.clint.drop(allocator);
-        self.plic.drop(allocator);
-        self.uart0.drop(allocator);
-        self.flash.drop(allocator);
-        self.dram.drop(allocator);
-    }
 }
 
 impl<A: Allocator> crate::system_bus::SystemBus<A> for SystemBus<A> {
     fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool {
-        let Some((resource, _)) = self.check_access(address, size) else {
-            return false;
-        };
-
-        match resource {
-            Resource::Mrom => !matches!(access_type, AccessType::Write),
-            Resource::Clint => size == 4 || size == 8,
-            Resource::Plic => size == 4,
-            Resource::Uart0 => true,
-            Resource::Flash => !matches!(access_type, AccessType::Write),
-            Resource::Dram => true,
-            Resource::PowerDown => matches!(access_type, AccessType::Write),
+        match self.check_access(address, size) {
+            Some((id, _)) => (self.devices[id.0 as usize].policy.accepted_access_types)(access_type),
+            None => false,
         }
     }
 }
 
 impl<A: Allocator> Bus<A> for SystemBus<A> {
     fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource).read(buf, allocator, mapped_address);
+        // If no device is being accessed, or the access is not valid, nothing happens.
+        if let Some((id, mapped_address)) = self.check_access(address, buf.len()) {
+            self.device(id).read(buf, allocator, mapped_address);
         }
     }
 
     fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource)
-                .read_debug(buf, allocator, mapped_address)
+        // If no device is being accessed, or the access is not valid, nothing happens.
+        if let Some((id, mapped_address)) = self.check_access(address, buf.len()) {
+            self.device(id).read_debug(buf, allocator, mapped_address)
         }
     }
 
     fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource).write(allocator, mapped_address, buf);
+        // If no device is being accessed, or the access is not valid, nothing happens.
+        if let Some((id, mapped_address)) = self.check_access(address, buf.len()) {
+            self.device(id).write(allocator, mapped_address, buf);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `SystemBus` itself is generic over `Allocator`, which this crate fragment doesn't vendor a
+    // usable implementation of, so `register_device`/`check_access`/`accepts` can't be exercised
+    // end-to-end here; the `AccessPolicy` predicates they delegate to are plain `fn` pointers with
+    // no `Allocator` dependency, so at least those are covered directly below.
+
+    #[test]
+    fn unrestricted_accepts_any_size_and_access_type() {
+        assert!((AccessPolicy::UNRESTRICTED.accepted_sizes)(1));
+        assert!((AccessPolicy::UNRESTRICTED.accepted_sizes)(3));
+        assert!((AccessPolicy::UNRESTRICTED.accepted_access_types)(AccessType::Write));
+    }
+
+    #[test]
+    fn read_only_rejects_writes_but_accepts_any_size() {
+        assert!((AccessPolicy::READ_ONLY.accepted_sizes)(7));
+        assert!(!(AccessPolicy::READ_ONLY.accepted_access_types)(AccessType::Write));
+        assert!((AccessPolicy::READ_ONLY.accepted_access_types)(AccessType::Load));
+    }
+
+    #[test]
+    fn write_only_word_accepts_only_a_4_byte_write() {
+        assert!((AccessPolicy::WRITE_ONLY_WORD.accepted_sizes)(4));
+        assert!(!(AccessPolicy::WRITE_ONLY_WORD.accepted_sizes)(8));
+        assert!((AccessPolicy::WRITE_ONLY_WORD.accepted_access_types)(AccessType::Write));
+        assert!(!(AccessPolicy::WRITE_ONLY_WORD.accepted_access_types)(AccessType::Load));
+    }
+
+    #[test]
+    fn word_or_double_word_accepts_exactly_4_or_8_bytes() {
+        assert!((AccessPolicy::WORD_OR_DOUBLE_WORD.accepted_sizes)(4));
+        assert!((AccessPolicy::WORD_OR_DOUBLE_WORD.accepted_sizes)(8));
+        assert!(!(AccessPolicy::WORD_OR_DOUBLE_WORD.accepted_sizes)(1));
+        assert!(!(AccessPolicy::WORD_OR_DOUBLE_WORD.accepted_sizes)(16));
+    }
+
+    #[test]
+    fn word_accepts_only_4_bytes() {
+        assert!((AccessPolicy::WORD.accepted_sizes)(4));
+        assert!(!(AccessPolicy::WORD.accepted_sizes)(1));
+        assert!(!(AccessPolicy::WORD.accepted_sizes)(8));
+    }
+}
@@ -0,0 +1,100 @@
+//! A non-deterministic scheduler for a multi-hart machine, deciding both which hart runs next and
+//! when a hart's buffered stores (see [`super::store_buffer`]) drain to memory. Every choice is
+//! drawn from an injected [`Rng`], so a whole session is reproducible given a fixed seed (see
+//! [`crate::board::system_bus::SystemBus::new_seeded`]).
+
+use rand::Rng;
+
+use super::store_buffer::HartId;
+
+/// Picks which hart executes next and how aggressively store buffers drain, given a source of
+/// randomness. Holds no RNG state itself; callers pass one in so that the whole machine's
+/// nondeterminism draws from a single, central, seedable stream.
+#[derive(Debug)]
+pub struct Scheduler {
+    harts: Vec<HartId>,
+}
+
+impl Scheduler {
+    pub fn new(harts: Vec<HartId>) -> Self {
+        assert!(!harts.is_empty(), "a scheduler needs at least one hart");
+        Scheduler { harts }
+    }
+
+    pub fn harts(&self) -> &[HartId] {
+        &self.harts
+    }
+
+    /// Picks the hart that should execute its next instruction.
+    pub fn pick_hart(&self, rng: &mut impl Rng) -> HartId {
+        self.harts[rng.gen_range(0..self.harts.len())]
+    }
+
+    /// Decides how many of a hart's `pending` buffered stores should drain to memory right now,
+    /// between `0` (no progress this step) and `pending` (full drain).
+    pub fn pick_drain_count(&self, rng: &mut impl Rng, pending: usize) -> usize {
+        if pending == 0 {
+            0
+        } else {
+            rng.gen_range(0..=pending)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn pick_hart_always_returns_the_only_hart() {
+        let scheduler = Scheduler::new(vec![HartId(0)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..16 {
+            assert_eq!(scheduler.pick_hart(&mut rng), HartId(0));
+        }
+    }
+
+    #[test]
+    fn pick_hart_only_ever_picks_a_registered_hart() {
+        let harts = vec![HartId(0), HartId(1), HartId(2)];
+        let scheduler = Scheduler::new(harts.clone());
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..64 {
+            assert!(harts.contains(&scheduler.pick_hart(&mut rng)));
+        }
+    }
+
+    #[test]
+    fn pick_drain_count_is_zero_when_nothing_is_pending() {
+        let scheduler = Scheduler::new(vec![HartId(0)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(scheduler.pick_drain_count(&mut rng, 0), 0);
+    }
+
+    #[test]
+    fn pick_drain_count_never_exceeds_pending() {
+        let scheduler = Scheduler::new(vec![HartId(0)]);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..64 {
+            assert!(scheduler.pick_drain_count(&mut rng, 5) <= 5);
+        }
+    }
+
+    #[test]
+    fn same_seed_reproduces_the_same_choices() {
+        let scheduler = Scheduler::new(vec![HartId(0), HartId(1), HartId(2), HartId(3)]);
+
+        let mut rng_a = StdRng::seed_from_u64(123);
+        let mut rng_b = StdRng::seed_from_u64(123);
+        for _ in 0..32 {
+            assert_eq!(scheduler.pick_hart(&mut rng_a), scheduler.pick_hart(&mut rng_b));
+            assert_eq!(
+                scheduler.pick_drain_count(&mut rng_a, 10),
+                scheduler.pick_drain_count(&mut rng_b, 10)
+            );
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! A store-buffered model of shared `dram`, giving each hart in a multi-hart system the kind of
+//! relaxed store-to-load forwarding RVWMO permits: a hart's own stores are visible to its own
+//! subsequent loads immediately, but stay invisible to every other hart until explicitly drained.
+
+use std::collections::{HashMap, VecDeque};
+
+use space_time::allocator::Allocator;
+
+use crate::bus::Bus;
+use crate::resources::ram::Ram;
+
+/// Identifies one hart among the harts sharing a [`StoreBufferedMemory`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct HartId(pub u32);
+
+/// Global, monotonically increasing sequence number assigned to every buffered store, used to
+/// order a hart's own buffered stores and to record what a hart has already observed at a given
+/// address.
+type Seq = u64;
+
+#[derive(Debug, Clone)]
+struct PendingStore {
+    seq: Seq,
+    address: u32,
+    bytes: Vec<u8>,
+}
+
+/// Wraps a [`Ram`] with a per-hart FIFO store buffer sitting between each hart and memory.
+///
+/// - [`Self::write`] enters a store into the issuing hart's buffer; it is forwarded to that
+///   hart's own subsequent [`Self::read`]s, but not yet applied to `dram`.
+/// - [`Self::drain`] (driven by a scheduler, see [`super::scheduler::Scheduler`]) applies a hart's
+///   oldest buffered stores to `dram`, in program order, making them visible to every hart.
+/// - [`Self::fence`] fully drains a hart's buffer, as required by `FENCE`, `FENCE.TSO`, and any
+///   AMO or `.aq`/`.rl` access, establishing a happens-before edge between the fence and whatever
+///   any hart observing it does next.
+#[derive(Debug)]
+pub struct StoreBufferedMemory<A: Allocator> {
+    dram: Ram<A>,
+    buffers: HashMap<HartId, VecDeque<PendingStore>>,
+    next_seq: Seq,
+    /// For each byte address, the highest sequence number among the stores applied to `dram` there
+    /// (by [`Self::drain`] or [`Self::write_direct`]), or absent if `dram` has never been written
+    /// at that address. Since harts drain independently, the byte in `dram` may come from an
+    /// older store than the one this number names. Consulted by [`Self::read`] so a byte forwarded
+    /// from a buffer can be compared against what's backing memory right now.
+    dram_seq: HashMap<u32, Seq>,
+    /// For each `(hart, address)` pair, the highest store sequence number that hart has observed
+    /// there so far (through its own buffered stores or through a `dram` read). Used only to
+    /// assert the single-thread coherence invariant described on [`Self::read`]; the read/write
+    /// paths above never need to consult it to behave correctly, since a hart's own buffer is
+    /// forwarded byte-by-byte in sequence order and `dram_seq` only ever moves forward.
+    observed: HashMap<(HartId, u32), Seq>,
+}
+
+/// A snapshot of everything [`StoreBufferedMemory`] tracks outside `dram` itself: each hart's
+/// still-pending buffer, plus the `dram_seq`/`observed`/`next_seq` bookkeeping built around it.
+/// None of this lives behind an [`Allocator`] handle (unlike `dram`, a plain [`Ram`]), so it isn't
+/// captured by an allocator-level snapshot; [`crate::board::system_bus::SystemBus::snapshot`] and
+/// [`SystemBus::restore`](crate::board::system_bus::SystemBus::restore) fold it into a
+/// [`crate::board::checkpoint::Checkpoint`] alongside the central RNG stream instead, via
+/// [`StoreBufferedMemory::state`]/[`StoreBufferedMemory::restore_state`].
+#[derive(Debug, Clone)]
+pub struct StoreBufferState {
+    buffers: HashMap<HartId, VecDeque<PendingStore>>,
+    next_seq: Seq,
+    dram_seq: HashMap<u32, Seq>,
+    observed: HashMap<(HartId, u32), Seq>,
+}
+
+impl<A: Allocator> StoreBufferedMemory<A> {
+    pub fn new(dram: Ram<A>) -> Self {
+        StoreBufferedMemory {
+            dram,
+            buffers: HashMap::new(),
+            next_seq: 0,
+            dram_seq: HashMap::new(),
+            observed: HashMap::new(),
+        }
+    }
+
+    /// Buffers a store from `hart`. Visible to `hart`'s own subsequent reads immediately; not
+    /// visible to any other hart until [`Self::drain`] or [`Self::fence`] applies it to `dram`.
+    pub fn write(&mut self, hart: HartId, address: u32, buf: &[u8]) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.buffers
+            .entry(hart)
+            .or_default()
+            .push_back(PendingStore {
+                seq,
+                address,
+                bytes: buf.to_vec(),
+            });
+        // Every byte the store covers, not just `address` itself, so `read`'s single-thread
+        // coherence assertion has the same per-byte coverage for a multi-byte store as it does
+        // for a multi-byte read.
+        mark_observed(&mut self.observed, hart, address, buf.len() as u32, seq);
+    }
+
+    /// Reads `buf.len()` bytes at `address` as observed by `hart`, resolved byte-by-byte: each
+    /// byte is forwarded from `hart`'s own most recent buffered store that covers it, if any (even
+    /// a store of a different address/width that merely overlaps), falling back to `dram`
+    /// (reflecting whatever has been drained so far, from any hart) for bytes no buffered store
+    /// covers. Own-store forwarding is unconditional: `hart`'s own pending store to a byte always
+    /// wins over whatever is in `dram`, regardless of whether some other hart's store has already
+    /// drained there, since a hart's own program order is all that governs what it sees of its own
+    /// writes.
+    ///
+    /// Single-thread coherence invariant: a hart must never observe an older store to an address
+    /// than one it has already observed there. The assertion below exists to catch a regression in
+    /// that guarantee rather than to enforce it.
+    pub fn read(&mut self, hart: HartId, allocator: &mut A, buf: &mut [u8], address: u32) {
+        let mut from_dram = vec![0u8; buf.len()];
+        self.dram.read(&mut from_dram, allocator, address);
+
+        let buffer = self.buffers.get(&hart);
+        for (i, out) in buf.iter_mut().enumerate() {
+            let byte_address = address.wrapping_add(i as u32);
+            let base_seq = self.dram_seq.get(&byte_address).copied().unwrap_or(0);
+            let (value, seq) = match buffer {
+                Some(buffer) => forward_from_buffer(buffer, byte_address, from_dram[i], base_seq),
+                None => (from_dram[i], base_seq),
+            };
+
+            *out = value;
+
+            let observed = self.observed.entry((hart, byte_address)).or_insert(seq);
+            debug_assert!(
+                *observed <= seq,
+                "hart {hart:?} observed a store to {byte_address:#010x} older than one it already saw",
+            );
+            *observed = seq.max(*observed);
+        }
+    }
+
+    /// Drains up to `count` of `hart`'s oldest buffered stores into `dram`, in program order.
+    pub fn drain(&mut self, hart: HartId, allocator: &mut A, count: usize) {
+        let Some(buffer) = self.buffers.get_mut(&hart) else {
+            return;
+        };
+        for pending in take_oldest(buffer, count) {
+            self.dram.write(allocator, pending.address, &pending.bytes);
+            bump_dram_seq(
+                &mut self.dram_seq,
+                pending.address,
+                pending.bytes.len() as u32,
+                pending.seq,
+            );
+        }
+    }
+
+    /// Reads `buf.len()` bytes directly from `dram`, bypassing every hart's store buffer. For bus
+    /// masters that aren't a hart and so don't participate in per-hart buffering (e.g.
+    /// [`crate::resources::dma::Dma`]), whose accesses should be immediately visible everywhere,
+    /// the same as anything already drained.
+    pub fn read_direct(&mut self, allocator: &mut A, buf: &mut [u8], address: u32) {
+        self.dram.read(buf, allocator, address);
+    }
+
+    /// Writes directly to `dram`, bypassing every hart's store buffer; see [`Self::read_direct`].
+    /// Bumps the sequence counter so a hart reading this address afterwards observes it as newer
+    /// than anything buffered or drained before it.
+    pub fn write_direct(&mut self, allocator: &mut A, address: u32, buf: &[u8]) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.dram.write(allocator, address, buf);
+        bump_dram_seq(&mut self.dram_seq, address, buf.len() as u32, seq);
+    }
+
+    /// Fully drains `hart`'s store buffer, as required by `FENCE`, `FENCE.TSO`, and any AMO or
+    /// `.aq`/`.rl` access.
+    pub fn fence(&mut self, hart: HartId, allocator: &mut A) {
+        let pending = self.buffers.get(&hart).map_or(0, VecDeque::len);
+        self.drain(hart, allocator, pending);
+    }
+
+    /// Number of `hart`'s stores that have not yet drained to `dram`.
+    pub fn pending(&self, hart: HartId) -> usize {
+        self.buffers.get(&hart).map_or(0, VecDeque::len)
+    }
+
+    /// Captures every hart's pending buffer and the bookkeeping built around it (see
+    /// [`StoreBufferState`]), for folding into a checkpoint alongside `dram`'s own allocator
+    /// snapshot.
+    pub fn state(&self) -> StoreBufferState {
+        StoreBufferState {
+            buffers: self.buffers.clone(),
+            next_seq: self.next_seq,
+            dram_seq: self.dram_seq.clone(),
+            observed: self.observed.clone(),
+        }
+    }
+
+    /// Restores previously captured state (see [`Self::state`]); pair with restoring `dram`'s
+    /// allocator snapshot so buffered-but-undrained stores roll back in lockstep with the memory
+    /// they would otherwise have drained into.
+    pub fn restore_state(&mut self, state: StoreBufferState) {
+        self.buffers = state.buffers;
+        self.next_seq = state.next_seq;
+        self.dram_seq = state.dram_seq;
+        self.observed = state.observed;
+    }
+}
+
+/// Scans `buffer` (one hart's own pending stores, oldest first) for the most recent entry
+/// covering `byte_address`, returning its byte and sequence number, or `(base_value, base_seq)`
+/// unchanged if none covers it. `base_value`/`base_seq` are whatever `dram` itself holds there
+/// (i.e. the most recent drain from any hart); a hart's own buffered store always wins over them
+/// regardless, since a hart's own program order alone governs what it sees of its own writes.
+fn forward_from_buffer(
+    buffer: &VecDeque<PendingStore>,
+    byte_address: u32,
+    base_value: u8,
+    base_seq: Seq,
+) -> (u8, Seq) {
+    let mut value = base_value;
+    let mut seq = base_seq;
+    for pending in buffer {
+        let covers = byte_address >= pending.address
+            && byte_address < pending.address.wrapping_add(pending.bytes.len() as u32);
+        if covers {
+            value = pending.bytes[(byte_address - pending.address) as usize];
+            seq = pending.seq;
+        }
+    }
+    (value, seq)
+}
+
+/// Pops up to `count` of `buffer`'s oldest entries, in FIFO (program) order, for the caller to
+/// apply to `dram`; fewer than `count` if the buffer runs out first (a full
+/// [`StoreBufferedMemory::fence`] passes `buffer.len()`).
+fn take_oldest(buffer: &mut VecDeque<PendingStore>, count: usize) -> Vec<PendingStore> {
+    let mut taken = Vec::with_capacity(count.min(buffer.len()));
+    for _ in 0..count {
+        let Some(pending) = buffer.pop_front() else {
+            break;
+        };
+        taken.push(pending);
+    }
+    taken
+}
+
+/// Records that `seq` has now been applied to `dram` for each of the `len` bytes starting at
+/// `address`, taking the max with whatever sequence number (if any) was already recorded there so
+/// `dram_seq` only ever moves forward, regardless of the order in which harts' stores happen to
+/// drain.
+fn bump_dram_seq(dram_seq: &mut HashMap<u32, Seq>, address: u32, len: u32, seq: Seq) {
+    for i in 0..len {
+        let byte_address = address.wrapping_add(i);
+        dram_seq
+            .entry(byte_address)
+            .and_modify(|s| *s = (*s).max(seq))
+            .or_insert(seq);
+    }
+}
+
+/// Records `hart` having stored to every byte in `address..address+len` at `seq`, the write-side
+/// counterpart of the per-byte update [`StoreBufferedMemory::read`] does on its own `observed`
+/// entries.
+fn mark_observed(
+    observed: &mut HashMap<(HartId, u32), Seq>,
+    hart: HartId,
+    address: u32,
+    len: u32,
+    seq: Seq,
+) {
+    for i in 0..len {
+        observed.insert((hart, address.wrapping_add(i)), seq);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(seq: Seq, address: u32, bytes: &[u8]) -> PendingStore {
+        PendingStore {
+            seq,
+            address,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn own_buffered_store_wins_over_a_newer_base_seq() {
+        // Mirrors a hart observing its own seq-0 store to an address after some other hart's
+        // later (seq 1) store has already drained there: `base_seq` being newer must not make
+        // the scan prefer `base_value` over the hart's own pending store.
+        let mut buffer = VecDeque::new();
+        buffer.push_back(pending(0, 0x100, &[1]));
+
+        let (value, seq) = forward_from_buffer(&buffer, 0x100, 2, 1);
+
+        assert_eq!(value, 1);
+        assert_eq!(seq, 0);
+    }
+
+    #[test]
+    fn most_recent_covering_store_wins() {
+        let mut buffer = VecDeque::new();
+        buffer.push_back(pending(0, 0x100, &[1]));
+        buffer.push_back(pending(1, 0x100, &[2]));
+
+        let (value, seq) = forward_from_buffer(&buffer, 0x100, 0, 0);
+
+        assert_eq!(value, 2);
+        assert_eq!(seq, 1);
+    }
+
+    #[test]
+    fn non_covering_store_falls_back_to_base() {
+        let mut buffer = VecDeque::new();
+        buffer.push_back(pending(0, 0x200, &[9]));
+
+        let (value, seq) = forward_from_buffer(&buffer, 0x100, 5, 3);
+
+        assert_eq!(value, 5);
+        assert_eq!(seq, 3);
+    }
+
+    #[test]
+    fn drain_takes_oldest_first_and_stops_when_empty() {
+        let mut buffer = VecDeque::new();
+        buffer.push_back(pending(0, 0x100, &[1]));
+        buffer.push_back(pending(1, 0x104, &[2]));
+
+        let taken = take_oldest(&mut buffer, 5);
+
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].seq, 0);
+        assert_eq!(taken[1].seq, 1);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn fence_style_full_drain_empties_the_buffer() {
+        let mut buffer = VecDeque::new();
+        buffer.push_back(pending(0, 0x100, &[1]));
+        buffer.push_back(pending(1, 0x104, &[2]));
+        buffer.push_back(pending(2, 0x108, &[3]));
+
+        let len = buffer.len();
+        let taken = take_oldest(&mut buffer, len);
+
+        assert_eq!(taken.len(), 3);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn bump_dram_seq_never_regresses() {
+        let mut dram_seq = HashMap::new();
+        bump_dram_seq(&mut dram_seq, 0x100, 1, 5);
+        bump_dram_seq(&mut dram_seq, 0x100, 1, 2);
+
+        assert_eq!(dram_seq[&0x100], 5);
+    }
+
+    #[test]
+    fn bump_dram_seq_covers_every_byte_in_range() {
+        let mut dram_seq = HashMap::new();
+        bump_dram_seq(&mut dram_seq, 0x100, 4, 7);
+
+        for address in 0x100..0x104 {
+            assert_eq!(dram_seq[&address], 7);
+        }
+        assert!(!dram_seq.contains_key(&0x104));
+    }
+
+    #[test]
+    fn mark_observed_covers_every_byte_in_range() {
+        let mut observed = HashMap::new();
+        let hart = HartId(0);
+        mark_observed(&mut observed, hart, 0x100, 4, 7);
+
+        for address in 0x100..0x104 {
+            assert_eq!(observed[&(hart, address)], 7);
+        }
+        assert!(!observed.contains_key(&(hart, 0x104)));
+    }
+
+    #[test]
+    fn mark_observed_is_keyed_per_hart() {
+        let mut observed = HashMap::new();
+        mark_observed(&mut observed, HartId(0), 0x100, 1, 3);
+
+        assert!(!observed.contains_key(&(HartId(1), 0x100)));
+    }
+}
@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write as _};
+use std::rc::Weak;
+
+use space_time::allocator::{Allocator, Region};
+
+use crate::board::system_bus::{DeviceId, SystemBus};
+use crate::bus::Bus;
+
+/// Offsets (from the device's base address) of the [`Block`] control registers and data window.
+mod reg {
+    /// Index of the sector the next [`cmd::READ`]/[`cmd::WRITE`] command operates on.
+    pub const SECTOR: u32 = 0x00;
+    /// Write a [`super::cmd`] value here to issue a command.
+    pub const CMD: u32 = 0x04;
+    /// See [`super::status`].
+    pub const STATUS: u32 = 0x08;
+    /// Start of the `SECTOR_SIZE`-byte data window: for [`super::cmd::READ`], filled with the
+    /// sector's contents once [`super::status::DONE`] is set; for [`super::cmd::WRITE`], written by
+    /// software before issuing the command.
+    pub const DATA: u32 = 0x0c;
+    /// Size, in bytes, of the register file, including the data window.
+    pub const SIZE: u32 = DATA + super::SECTOR_SIZE as u32;
+}
+
+/// Commands accepted by the [`reg::CMD`] register.
+mod cmd {
+    /// Read sector [`reg::SECTOR`] from the backing file into the data window.
+    pub const READ: u32 = 1;
+    /// Write the data window to sector [`reg::SECTOR`] of the backing file.
+    pub const WRITE: u32 = 2;
+}
+
+/// Bit layout of the [`Block`] status register (`reg::STATUS`).
+mod status {
+    /// Set by hardware once the last command completes, regardless of whether it errored.
+    /// Cleared by software writing 1 to this bit (write-1-to-clear).
+    pub const DONE: u32 = 1 << 0;
+    /// Set by hardware if the last command's backing-file I/O failed (e.g. a read or write past
+    /// the end of the file). Cleared alongside `DONE`.
+    pub const ERROR: u32 = 1 << 1;
+}
+
+/// Sector size, in bytes, of a [`Block`] device.
+const SECTOR_SIZE: usize = 512;
+
+#[derive(Copy, Clone)]
+struct State {
+    sector: u32,
+    status: u32,
+    data: [u8; SECTOR_SIZE],
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            sector: 0,
+            status: 0,
+            data: [0; SECTOR_SIZE],
+        }
+    }
+}
+
+/// A read/write, sector-addressed block device backed by a host file, unlike the read-only
+/// [`crate::resources::rom::Rom`]/flash devices.
+///
+/// Software picks a sector with [`reg::SECTOR`], stages or collects its contents through the
+/// `reg::DATA` window, and issues [`cmd::READ`]/[`cmd::WRITE`] through [`reg::CMD`]; completion
+/// (successful or not) is reported through [`reg::STATUS`], mirroring the command/status protocol
+/// [`crate::resources::dma::Dma`] uses.
+///
+/// Unlike every other device in this module, a `Block`'s state is split across the allocator (the
+/// `sector`/`status` registers and the in-flight data window, all snapshotted along with the rest
+/// of the machine) and a host file that is *not* part of any [`space_time::allocator::Allocator`]
+/// snapshot. To stay compatible with [`crate::board::checkpoint`] anyway, every sector actually
+/// read from the host file is recorded via [`SystemBus::record_host_read`], so a replay can feed
+/// the exact same bytes back without the backing file needing to exist, or still hold the same
+/// contents, at replay time. Sector writes are not recorded: they only ever reproduce bytes that
+/// came from the data window, which is itself allocator state and thus already covered by
+/// snapshotting.
+///
+/// The register file (`SECTOR`/`CMD`/`STATUS`) only accepts word-sized accesses, while the data
+/// window below it accepts any size; since a single [`crate::board::system_bus::AccessPolicy`]
+/// applies to the whole registered range, register `Block` with
+/// [`crate::board::system_bus::AccessPolicy::UNRESTRICTED`] and let [`Bus::read`]/[`Bus::write`]
+/// reject the wrong width themselves (same as an access to an unmapped address would: silently do
+/// nothing).
+#[derive(Debug)]
+pub struct Block<A: Allocator> {
+    file: RefCell<File>,
+    state: Region<State>,
+    bus: Weak<SystemBus<A>>,
+    id: DeviceId,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("sector", &self.sector)
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<A: Allocator> Block<A> {
+    /// Size, in bytes, of the MMIO register file exposed by a [`Block`] device. Used when
+    /// registering it with [`SystemBus::register_device`].
+    pub const REGISTER_FILE_SIZE: u32 = reg::SIZE;
+
+    /// Creates a new `Block` device backed by `file`. `id` is the [`DeviceId`] this device is
+    /// being registered under, used to key its [`SystemBus::record_host_read`] calls; construct
+    /// via [`SystemBus::register_device_with`], which supplies it before the device needs to know
+    /// its final address range.
+    pub fn new(allocator: &mut A, file: File, bus: Weak<SystemBus<A>>, id: DeviceId) -> Self {
+        Block {
+            file: RefCell::new(file),
+            state: allocator.alloc(State::default()),
+            bus,
+            id,
+        }
+    }
+
+    /// Services a [`cmd::READ`] by preferring a replayed sector over the backing file, the same
+    /// way [`crate::resources::uart_io::Uart0::poll`] prefers a replayed RX byte over its live
+    /// source: a replay should see the exact bytes the original recording captured rather than
+    /// whatever the (possibly absent, possibly different) backing file holds now. Only a live
+    /// read is recorded via [`SystemBus::record_host_read`]; a replayed one is already in the
+    /// log it came from.
+    fn read_sector(&self, offset: u64) -> std::io::Result<[u8; SECTOR_SIZE]> {
+        let replayed = self
+            .bus
+            .upgrade()
+            .and_then(|bus| bus.poll_host_read(self.id));
+        if let Some(bytes) = replayed {
+            if bytes.len() != SECTOR_SIZE {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "replayed sector has the wrong length",
+                ));
+            }
+            let mut data = [0u8; SECTOR_SIZE];
+            data.copy_from_slice(&bytes);
+            return Ok(data);
+        }
+
+        let mut data = [0u8; SECTOR_SIZE];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut data))?;
+        drop(file);
+        if let Some(bus) = self.bus.upgrade() {
+            bus.record_host_read(self.id, &data);
+        }
+        Ok(data)
+    }
+
+    fn run_command(&self, allocator: &mut A, command: u32) {
+        let sector = allocator.get(self.state).sector;
+        let offset = sector as u64 * SECTOR_SIZE as u64;
+
+        let result = match command {
+            cmd::READ => self.read_sector(offset).map(Some),
+            cmd::WRITE => {
+                let data = allocator.get(self.state).data;
+                let mut file = self.file.borrow_mut();
+                file.seek(SeekFrom::Start(offset))
+                    .and_then(|_| file.write_all(&data))
+                    .map(|()| None)
+            }
+            _ => return,
+        };
+
+        let read = match result {
+            Ok(read) => read,
+            Err(_) => {
+                allocator.get_mut(self.state).status = status::DONE | status::ERROR;
+                return;
+            }
+        };
+
+        let state = allocator.get_mut(self.state);
+        state.status = status::DONE;
+        if let Some(data) = read {
+            state.data = data;
+        }
+    }
+}
+
+impl<A: Allocator> Bus<A> for Block<A> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+        let state = allocator.get(self.state);
+        match address {
+            reg::SECTOR if buf.len() == 4 => buf.copy_from_slice(&state.sector.to_le_bytes()),
+            reg::STATUS if buf.len() == 4 => buf.copy_from_slice(&state.status.to_le_bytes()),
+            offset if (reg::DATA..reg::SIZE).contains(&offset)
+                && offset as usize + buf.len() <= reg::SIZE as usize =>
+            {
+                let start = (offset - reg::DATA) as usize;
+                buf.copy_from_slice(&state.data[start..start + buf.len()]);
+            }
+            // A register read of the wrong width (the register file is word-addressed; only the
+            // data window below it accepts arbitrary sizes) does nothing, same as an access to an
+            // unmapped address would.
+            _ => {}
+        }
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+        let state = allocator.get(self.state);
+        match address {
+            reg::SECTOR if buf.len() == 4 => buf.copy_from_slice(&state.sector.to_le_bytes()),
+            reg::STATUS if buf.len() == 4 => buf.copy_from_slice(&state.status.to_le_bytes()),
+            offset if (reg::DATA..reg::SIZE).contains(&offset)
+                && offset as usize + buf.len() <= reg::SIZE as usize =>
+            {
+                let start = (offset - reg::DATA) as usize;
+                buf.copy_from_slice(&state.data[start..start + buf.len()]);
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        match address {
+            reg::SECTOR if buf.len() == 4 => {
+                allocator.get_mut(self.state).sector = u32::from_le_bytes(buf.try_into().unwrap());
+            }
+            reg::CMD if buf.len() == 4 => {
+                let command = u32::from_le_bytes(buf.try_into().unwrap());
+                self.run_command(allocator, command);
+            }
+            reg::STATUS if buf.len() == 4 => {
+                let value = u32::from_le_bytes(buf.try_into().unwrap());
+                if value & (status::DONE | status::ERROR) != 0 {
+                    allocator.get_mut(self.state).status &= !(value & (status::DONE | status::ERROR));
+                }
+            }
+            offset if (reg::DATA..reg::SIZE).contains(&offset)
+                && offset as usize + buf.len() <= reg::SIZE as usize =>
+            {
+                let start = (offset - reg::DATA) as usize;
+                allocator.get_mut(self.state).data[start..start + buf.len()].copy_from_slice(buf);
+            }
+            // A register write of the wrong width does nothing, same as an access to an unmapped
+            // address would; the data window below the register file still accepts any size.
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! A minimal RISC-V CLINT (core-local interruptor): per-hart `msip`/`mtimecmp` registers plus a
+//! machine-wide `mtime` counter, following the de facto SiFive register layout.
+
+use space_time::allocator::{Allocator, Region};
+
+use crate::bus::Bus;
+use crate::core::store_buffer::HartId;
+
+/// Offsets (from the device's base address) of the [`Clint`] register file: a [`reg::msip`] word
+/// per hart starting at [`reg::MSIP_BASE`], an [`reg::mtimecmp_lo`]/[`reg::mtimecmp_hi`] word pair
+/// per hart starting at [`reg::MTIMECMP_BASE`], and a single [`reg::MTIME_LO`]/[`reg::MTIME_HI`]
+/// pair shared by every hart. All registers are word-sized, split into a lo/hi pair where the
+/// underlying value is 64 bits, the same way a 32-bit hart would address them in real hardware.
+mod reg {
+    /// Base offset of the per-hart `msip` word array; hart index `i`'s register is at
+    /// `MSIP_BASE + 4 * i`. Only bit 0 is implemented: software interrupt pending.
+    pub const MSIP_BASE: u32 = 0x0000;
+    /// Base offset of the per-hart `mtimecmp` word-pair array; hart index `i`'s low/high words are
+    /// at `MTIMECMP_BASE + 8 * i` and `MTIMECMP_BASE + 8 * i + 4`.
+    pub const MTIMECMP_BASE: u32 = 0x4000;
+    /// Fixed offset of the machine-wide `mtime` counter's low word, regardless of hart count
+    /// (mirroring real CLINT hardware, which reserves `0x4000..0xbff8` for up to 4095 harts'
+    /// `mtimecmp`s).
+    pub const MTIME_LO: u32 = 0xbff8;
+    pub const MTIME_HI: u32 = 0xbffc;
+    /// Size, in bytes, of the register file.
+    pub const SIZE: u32 = MTIME_HI + 4;
+
+    pub fn msip(hart_index: u32) -> u32 {
+        MSIP_BASE + 4 * hart_index
+    }
+
+    pub fn mtimecmp_lo(hart_index: u32) -> u32 {
+        MTIMECMP_BASE + 8 * hart_index
+    }
+
+    pub fn mtimecmp_hi(hart_index: u32) -> u32 {
+        MTIMECMP_BASE + 8 * hart_index + 4
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    msip: Vec<bool>,
+    mtimecmp: Vec<u64>,
+    mtime: u64,
+}
+
+/// A CLINT serving the harts in [`Self::harts`] order: hart `harts()[i]`'s registers live at
+/// index `i` in the [`reg`] layout.
+///
+/// Unlike [`crate::resources::plic::Plic`], a CLINT's interrupt lines are hart-local rather than
+/// routed through a shared controller, so this device never raises anything through
+/// [`crate::board::system_bus::SystemBus::get_plic_irq_callback`]. Instead, [`Self::msip`] (for
+/// inter-hart IPIs) and [`Self::timer_pending`] (`mtime >= mtimecmp`) are meant to be polled
+/// directly by whatever drives each hart's `mip.MSIP`/`mip.MTIP` bits, and [`Self::advance_time`]
+/// is meant to be driven once per unit of simulated wall-clock time — both outside this crate,
+/// the same way a hart's instruction execution itself is.
+#[derive(Debug)]
+pub struct Clint<A: Allocator> {
+    state: Region<State>,
+    harts: Vec<HartId>,
+}
+
+impl<A: Allocator> Clint<A> {
+    /// Size, in bytes, of the MMIO register file exposed by a [`Clint`] device. Used when
+    /// registering it with [`crate::board::system_bus::SystemBus::register_device`], under
+    /// [`crate::board::system_bus::AccessPolicy::WORD_OR_DOUBLE_WORD`] so a 64-bit hart's
+    /// single-instruction `mtimecmp`/`mtime` accesses reach [`Bus::read`]/[`Bus::write`] intact.
+    pub const REGISTER_FILE_SIZE: u32 = reg::SIZE;
+
+    /// Creates a new `Clint` serving `harts`, with every `msip`/`mtimecmp` cleared and `mtime`
+    /// starting at 0.
+    pub fn new(allocator: &mut A, harts: Vec<HartId>) -> Self {
+        assert!(!harts.is_empty(), "a CLINT needs at least one hart");
+        assert!(
+            harts.len() <= ((reg::MTIME_LO - reg::MTIMECMP_BASE) / 8) as usize,
+            "too many harts for this CLINT's mtimecmp array to fit before mtime"
+        );
+        let state = State {
+            msip: vec![false; harts.len()],
+            mtimecmp: vec![0; harts.len()],
+            mtime: 0,
+        };
+        Clint {
+            state: allocator.alloc(state),
+            harts,
+        }
+    }
+
+    /// The harts this CLINT serves, in register-index order.
+    pub fn harts(&self) -> &[HartId] {
+        &self.harts
+    }
+
+    fn hart_index(&self, hart: HartId) -> Option<u32> {
+        self.harts.iter().position(|&h| h == hart).map(|i| i as u32)
+    }
+
+    /// Whether `hart`'s software interrupt (set by another hart writing its `msip`, i.e. an IPI)
+    /// is pending.
+    pub fn msip(&self, allocator: &A, hart: HartId) -> bool {
+        let Some(index) = self.hart_index(hart) else {
+            return false;
+        };
+        allocator.get(self.state).msip[index as usize]
+    }
+
+    /// Whether `hart`'s timer interrupt is pending (`mtime >= hart`'s `mtimecmp`).
+    pub fn timer_pending(&self, allocator: &A, hart: HartId) -> bool {
+        let Some(index) = self.hart_index(hart) else {
+            return false;
+        };
+        let state = allocator.get(self.state);
+        state.mtime >= state.mtimecmp[index as usize]
+    }
+
+    /// Advances the shared `mtime` counter by `delta`, driving every hart's [`Self::timer_pending`]
+    /// forward in lockstep.
+    pub fn advance_time(&self, allocator: &mut A, delta: u64) {
+        let state = allocator.get_mut(self.state);
+        state.mtime = state.mtime.wrapping_add(delta);
+    }
+}
+
+impl<A: Allocator> Bus<A> for Clint<A> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+        self.read_debug(buf, allocator, address);
+    }
+
+    /// Registered under [`crate::board::system_bus::AccessPolicy::WORD_OR_DOUBLE_WORD`], so `buf`
+    /// is either one word or two consecutive words; each is resolved independently by
+    /// [`word_value`], the same way a pair of back-to-back word accesses would be. This happens to
+    /// do the right thing for a double-word access to `mtimecmp`/`mtime` (reading the lo/hi halves
+    /// in one go, as a 64-bit hart's `LD` would), since each pair is laid out contiguously; a
+    /// double-word access straddling two unrelated registers (e.g. two harts' `msip`s) just reads
+    /// both independently, which is harmless since nothing depends on it.
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+        let state = allocator.get(self.state);
+        let hart_count = self.harts.len();
+        let word_count = buf.len() / 4;
+        // Resolved into a temporary first so a word that doesn't map to anything leaves the whole
+        // access a no-op, rather than partially overwriting `buf`.
+        let mut words = [0u32; 2];
+        for (i, word) in words[..word_count].iter_mut().enumerate() {
+            let Some(value) = word_value(state, address.wrapping_add(i as u32 * 4), hart_count)
+            else {
+                return;
+            };
+            *word = value;
+        }
+        for (i, chunk) in buf.chunks_mut(4).enumerate() {
+            chunk.copy_from_slice(&words[i].to_le_bytes());
+        }
+    }
+
+    /// See [`Self::read_debug`] for how a double-word access is split into independent word
+    /// accesses.
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        let hart_count = self.harts.len();
+        let state = allocator.get_mut(self.state);
+        for (i, chunk) in buf.chunks(4).enumerate() {
+            let value = u32::from_le_bytes(chunk.try_into().unwrap());
+            write_word(state, address.wrapping_add(i as u32 * 4), value, hart_count);
+        }
+    }
+}
+
+/// Resolves the value of the single 4-byte-aligned word at `word_address`, or `None` if it
+/// doesn't map to any register.
+fn word_value(state: &State, word_address: u32, hart_count: usize) -> Option<u32> {
+    if word_address == reg::MTIME_LO {
+        Some(state.mtime as u32)
+    } else if word_address == reg::MTIME_HI {
+        Some((state.mtime >> 32) as u32)
+    } else if let Some(index) = msip_index(word_address, hart_count) {
+        Some(state.msip[index] as u32)
+    } else if let Some(index) = mtimecmp_lo_index(word_address, hart_count) {
+        Some(state.mtimecmp[index] as u32)
+    } else if let Some(index) = mtimecmp_hi_index(word_address, hart_count) {
+        Some((state.mtimecmp[index] >> 32) as u32)
+    } else {
+        None
+    }
+}
+
+/// Writes `value` to the single 4-byte-aligned word at `word_address`, doing nothing if it
+/// doesn't map to any register.
+fn write_word(state: &mut State, word_address: u32, value: u32, hart_count: usize) {
+    if word_address == reg::MTIME_LO {
+        state.mtime = (state.mtime & !0xffff_ffff) | value as u64;
+    } else if word_address == reg::MTIME_HI {
+        state.mtime = (state.mtime & 0xffff_ffff) | ((value as u64) << 32);
+    } else if let Some(index) = msip_index(word_address, hart_count) {
+        // Only bit 0 is architecturally defined; higher bits are reserved and ignored.
+        state.msip[index] = value & 1 != 0;
+    } else if let Some(index) = mtimecmp_lo_index(word_address, hart_count) {
+        state.mtimecmp[index] = (state.mtimecmp[index] & !0xffff_ffff) | value as u64;
+    } else if let Some(index) = mtimecmp_hi_index(word_address, hart_count) {
+        state.mtimecmp[index] = (state.mtimecmp[index] & 0xffff_ffff) | ((value as u64) << 32);
+    }
+}
+
+fn msip_index(address: u32, hart_count: usize) -> Option<usize> {
+    if address >= reg::MTIMECMP_BASE || address % 4 != 0 {
+        return None;
+    }
+    let index = (address / 4) as usize;
+    (index < hart_count).then_some(index)
+}
+
+fn mtimecmp_lo_index(address: u32, hart_count: usize) -> Option<usize> {
+    if address < reg::MTIMECMP_BASE || address >= reg::MTIME_LO || address % 8 != 0 {
+        return None;
+    }
+    let index = ((address - reg::MTIMECMP_BASE) / 8) as usize;
+    (index < hart_count).then_some(index)
+}
+
+fn mtimecmp_hi_index(address: u32, hart_count: usize) -> Option<usize> {
+    if address < reg::MTIMECMP_BASE || address >= reg::MTIME_LO || address % 8 != 4 {
+        return None;
+    }
+    let index = ((address - reg::MTIMECMP_BASE - 4) / 8) as usize;
+    (index < hart_count).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(hart_count: usize) -> State {
+        State {
+            msip: vec![false; hart_count],
+            mtimecmp: vec![0; hart_count],
+            mtime: 0,
+        }
+    }
+
+    #[test]
+    fn word_value_reads_mtime_lo_and_hi_independently() {
+        let mut s = state(1);
+        s.mtime = 0x1122_3344_5566_7788;
+        assert_eq!(word_value(&s, reg::MTIME_LO, 1), Some(0x5566_7788));
+        assert_eq!(word_value(&s, reg::MTIME_HI, 1), Some(0x1122_3344));
+    }
+
+    #[test]
+    fn word_value_reads_mtimecmp_lo_and_hi_for_the_right_hart() {
+        let mut s = state(2);
+        s.mtimecmp[1] = 0xaabb_ccdd_eeff_0011;
+        assert_eq!(word_value(&s, reg::mtimecmp_lo(1), 2), Some(0xeeff_0011));
+        assert_eq!(word_value(&s, reg::mtimecmp_hi(1), 2), Some(0xaabb_ccdd));
+        // Hart 0's mtimecmp is untouched.
+        assert_eq!(word_value(&s, reg::mtimecmp_lo(0), 2), Some(0));
+    }
+
+    #[test]
+    fn word_value_reads_msip_as_zero_or_one() {
+        let mut s = state(2);
+        s.msip[1] = true;
+        assert_eq!(word_value(&s, reg::msip(0), 2), Some(0));
+        assert_eq!(word_value(&s, reg::msip(1), 2), Some(1));
+    }
+
+    #[test]
+    fn word_value_rejects_addresses_outside_any_hart() {
+        // A doubleword access to the last hart's msip spills one word past the msip array, into
+        // the gap before MTIMECMP_BASE that maps to no register.
+        assert_eq!(word_value(&state(1), reg::msip(1), 1), None);
+    }
+
+    #[test]
+    fn write_word_updates_mtime_halves_independently() {
+        let mut s = state(1);
+        write_word(&mut s, reg::MTIME_LO, 0x5566_7788, 1);
+        write_word(&mut s, reg::MTIME_HI, 0x1122_3344, 1);
+        assert_eq!(s.mtime, 0x1122_3344_5566_7788);
+    }
+
+    #[test]
+    fn write_word_updates_mtimecmp_halves_independently() {
+        let mut s = state(1);
+        write_word(&mut s, reg::mtimecmp_lo(0), 0xeeff_0011, 1);
+        write_word(&mut s, reg::mtimecmp_hi(0), 0xaabb_ccdd, 1);
+        assert_eq!(s.mtimecmp[0], 0xaabb_ccdd_eeff_0011);
+    }
+
+    #[test]
+    fn write_word_masks_msip_to_a_single_bit() {
+        let mut s = state(1);
+        write_word(&mut s, reg::msip(0), 0xffff_fffe, 1);
+        assert!(!s.msip[0]);
+        write_word(&mut s, reg::msip(0), 0xffff_ffff, 1);
+        assert!(s.msip[0]);
+    }
+
+    #[test]
+    fn write_word_to_an_unmapped_address_is_a_no_op() {
+        let mut s = state(1);
+        write_word(&mut s, reg::msip(5), 1, 1);
+        assert_eq!(s, state(1));
+    }
+}
@@ -0,0 +1,295 @@
+use std::rc::Weak;
+
+use space_time::allocator::{Allocator, Region};
+
+use crate::board::system_bus::SystemBus;
+use crate::bus::Bus;
+use crate::interrupt::DynIrqCallback;
+use crate::system_bus::{AccessType, SystemBus as _};
+
+/// Offsets (from the device's base address) of the [`Dma`] control registers.
+mod reg {
+    /// Source physical address for the next transfer.
+    pub const SRC: u32 = 0x00;
+    /// Destination physical address for the next transfer.
+    pub const DST: u32 = 0x04;
+    /// Number of bytes to copy.
+    pub const LEN: u32 = 0x08;
+    /// Control/status word, see [`super::ctrl`].
+    pub const CTRL: u32 = 0x0c;
+    /// Size, in bytes, of the register file.
+    pub const SIZE: u32 = 0x10;
+}
+
+/// Number of bytes moved per sub-access issued against the owning [`SystemBus`]. A transfer whose
+/// `len` isn't a multiple of this is completed byte-by-byte for the remainder.
+const CHUNK: u32 = 4;
+
+/// Bit layout of the [`Dma`] control/status register (`reg::CTRL`).
+mod ctrl {
+    /// Write 1 to start a transfer using the current `SRC`/`DST`/`LEN` registers; read back as 1
+    /// while the transfer is in progress (it always completes within the same `write` call, so
+    /// this will only ever read back as 0 from software's perspective).
+    pub const GO: u32 = 1 << 0;
+    /// Set by hardware once a transfer completes, regardless of whether it encountered an error.
+    /// Cleared by software writing 1 to this bit (write-1-to-clear), which also lowers the
+    /// completion interrupt.
+    pub const DONE: u32 = 1 << 1;
+    /// Set by hardware if any sub-access of the last transfer was rejected by the bus (unmapped
+    /// or misaligned). The transfer is truncated at the failing sub-access rather than aborted
+    /// outright. Cleared alongside `DONE`.
+    pub const ERROR: u32 = 1 << 2;
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Registers {
+    src: u32,
+    dst: u32,
+    len: u32,
+    ctrl: u32,
+}
+
+/// A simple block-transfer DMA engine, mastering the [`SystemBus`] it is attached to.
+///
+/// Software programs `SRC`, `DST` and `LEN`, then writes [`ctrl::GO`] to `CTRL` to kick off a
+/// transfer. The engine issues the transfer as a sequence of `CHUNK`-sized (falling back to
+/// byte-sized for the remainder) reads from `SRC` followed by writes to `DST` against the bus's
+/// own [`Bus::read`]/[`Bus::write`], so it can move data between any two devices the bus can
+/// reach, including device-to-memory and memory-to-memory transfers, as long as main memory is
+/// itself registered as a slave via
+/// [`crate::board::system_bus::SystemBus::register_dram`] (it isn't reachable through
+/// [`Bus::read`]/[`Bus::write`] otherwise, since harts normally reach it through their own store
+/// buffers instead; see [`crate::core::store_buffer::StoreBufferedMemory`]). `SRC == DST` aside,
+/// the two regions are not required to be disjoint; overlap is handled like `memmove`, copying
+/// high-to-low (byte-by-byte) when `DST` overruns `SRC`, low-to-high (`CHUNK`-sized where
+/// possible) otherwise.
+///
+/// Each sub-access is checked against [`crate::system_bus::SystemBus::accepts`] first; a rejected
+/// sub-access (unmapped, misaligned, or wrong size for the destination's
+/// [`crate::board::system_bus::AccessPolicy`]) truncates the transfer there and sets
+/// [`ctrl::ERROR`], rather than aborting it outright or panicking.
+///
+/// On completion (whether or not an error was hit), the engine raises a PLIC interrupt through the
+/// same [`SystemBus::get_plic_irq_callback`] mechanism other devices use; software acknowledges it
+/// by writing 1 to [`ctrl::DONE`].
+#[derive(Debug)]
+pub struct Dma<A: Allocator> {
+    registers: Region<Registers>,
+    /// Weak reference to the bus this device masters, mirroring the pattern used by
+    /// `PlicIrqCallback` to let a device call back into the bus it's attached to without creating
+    /// a reference cycle.
+    bus: Weak<SystemBus<A>>,
+    irq: DynIrqCallback<A>,
+}
+
+impl<A: Allocator> Dma<A> {
+    /// Size, in bytes, of the MMIO register file exposed by a [`Dma`] device. Used when
+    /// registering it with [`SystemBus::register_device`], under
+    /// [`crate::board::system_bus::AccessPolicy::WORD`] (see the [`Bus`] impl below).
+    pub const REGISTER_FILE_SIZE: u32 = reg::SIZE;
+
+    /// Creates a new, idle DMA engine mastering `bus`, raising completion interrupts through
+    /// `irq`.
+    pub fn new(allocator: &mut A, bus: Weak<SystemBus<A>>, irq: DynIrqCallback<A>) -> Self {
+        Dma {
+            registers: allocator.alloc(Registers::default()),
+            bus,
+            irq,
+        }
+    }
+
+    fn start_transfer(&self, allocator: &mut A) {
+        let Some(bus) = self.bus.upgrade() else {
+            return;
+        };
+
+        let Registers { src, dst, len, .. } = *allocator.get(self.registers);
+
+        let mut error = false;
+        if overlaps_with_dst_ahead(src, dst, len) {
+            // Byte-by-byte is simplest to get right here; this path is only hit by the rare
+            // overlapping-descending case, not the common disjoint-regions transfer.
+            let mut offset = len;
+            while offset > 0 {
+                offset -= 1;
+                let Some(from) = src.checked_add(offset) else {
+                    error = true;
+                    break;
+                };
+                let Some(to) = dst.checked_add(offset) else {
+                    error = true;
+                    break;
+                };
+
+                if !bus.accepts(from, 1, AccessType::Load) || !bus.accepts(to, 1, AccessType::Write)
+                {
+                    error = true;
+                    break;
+                }
+
+                let mut buf = [0u8; 1];
+                bus.read(&mut buf, allocator, from);
+                bus.write(allocator, to, &buf);
+            }
+        } else {
+            let mut offset = 0u32;
+            while offset < len {
+                let Some(from) = src.checked_add(offset) else {
+                    error = true;
+                    break;
+                };
+                let Some(to) = dst.checked_add(offset) else {
+                    error = true;
+                    break;
+                };
+
+                let size = chunk_size(len - offset, from, to);
+
+                if !bus.accepts(from, size as usize, AccessType::Load)
+                    || !bus.accepts(to, size as usize, AccessType::Write)
+                {
+                    error = true;
+                    break;
+                }
+
+                let mut buf = [0u8; CHUNK as usize];
+                let buf = &mut buf[..size as usize];
+                bus.read(buf, allocator, from);
+                bus.write(allocator, to, buf);
+
+                offset += size;
+            }
+        }
+
+        let registers = allocator.get_mut(self.registers);
+        registers.ctrl = ctrl::DONE | if error { ctrl::ERROR } else { 0 };
+        self.irq.raise(allocator);
+    }
+}
+
+/// All registers are word-sized; register with
+/// [`crate::board::system_bus::AccessPolicy::WORD`] (as the PLIC does) so the bus rejects any
+/// other access width before it reaches [`Bus::read`]/[`Bus::write`]. The `buf.len() == 4` guards
+/// below are defense in depth against registering this device under a looser policy by mistake,
+/// matching [`crate::resources::block::Block`]'s per-register guards.
+impl<A: Allocator> Bus<A> for Dma<A> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+        if buf.len() != 4 {
+            return;
+        }
+        let registers = allocator.get(self.registers);
+        let value = match address {
+            reg::SRC => registers.src,
+            reg::DST => registers.dst,
+            reg::LEN => registers.len,
+            reg::CTRL => registers.ctrl & !ctrl::GO,
+            _ => return,
+        };
+        buf.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+        if buf.len() != 4 {
+            return;
+        }
+        let registers = allocator.get(self.registers);
+        let value = match address {
+            reg::SRC => registers.src,
+            reg::DST => registers.dst,
+            reg::LEN => registers.len,
+            reg::CTRL => registers.ctrl & !ctrl::GO,
+            _ => return,
+        };
+        buf.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        let Ok(bytes) = buf.try_into() else {
+            return;
+        };
+        let value = u32::from_le_bytes(bytes);
+        match address {
+            reg::SRC => allocator.get_mut(self.registers).src = value,
+            reg::DST => allocator.get_mut(self.registers).dst = value,
+            reg::LEN => allocator.get_mut(self.registers).len = value,
+            reg::CTRL if value & (ctrl::DONE | ctrl::ERROR) != 0 => {
+                let registers = allocator.get_mut(self.registers);
+                registers.ctrl &= !(value & (ctrl::DONE | ctrl::ERROR));
+                self.irq.lower(allocator);
+            }
+            reg::CTRL if value & ctrl::GO != 0 => self.start_transfer(allocator),
+            _ => {}
+        }
+    }
+}
+
+/// Whether `src..src+len` and `dst..dst+len` overlap with `dst` ahead of `src`, the one
+/// arrangement where copying low-to-high would clobber source bytes a later chunk still needs to
+/// read (see [`Dma::start_transfer`]). `false` if either range's end address overflows `u32`,
+/// since the in-progress transfer loop's own `checked_add` already turns that into an `error`.
+fn overlaps_with_dst_ahead(src: u32, dst: u32, len: u32) -> bool {
+    matches!(
+        (src.checked_add(len), dst.checked_add(len)),
+        (Some(src_end), Some(dst_end)) if dst > src && src < dst_end && dst < src_end
+    )
+}
+
+/// Picks the size of the next ascending-order sub-access: `CHUNK` if at least that many bytes
+/// `remain` and both `from`/`to` are `CHUNK`-aligned, one byte otherwise (covering both a
+/// misaligned start/end and the final, shorter-than-`CHUNK` remainder).
+fn chunk_size(remaining: u32, from: u32, to: u32) -> u32 {
+    if remaining >= CHUNK && from % CHUNK == 0 && to % CHUNK == 0 {
+        CHUNK
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlap_with_dst_ahead_is_detected() {
+        // The exact scenario from the bug report: src=0x1000, dst=0x1004, len=16.
+        assert!(overlaps_with_dst_ahead(0x1000, 0x1004, 16));
+    }
+
+    #[test]
+    fn overlap_with_dst_behind_is_not_descending() {
+        // dst < src with overlap is safe to copy ascending: an earlier chunk's write lands
+        // behind where a later chunk still needs to read from.
+        assert!(!overlaps_with_dst_ahead(0x1004, 0x1000, 16));
+    }
+
+    #[test]
+    fn disjoint_regions_are_not_descending() {
+        assert!(!overlaps_with_dst_ahead(0x1000, 0x2000, 16));
+    }
+
+    #[test]
+    fn adjacent_non_overlapping_regions_are_not_descending() {
+        assert!(!overlaps_with_dst_ahead(0x1000, 0x1010, 16));
+    }
+
+    #[test]
+    fn overflowing_end_address_is_not_descending() {
+        assert!(!overlaps_with_dst_ahead(0x1000, u32::MAX - 4, 16));
+    }
+
+    #[test]
+    fn chunk_size_prefers_chunk_when_aligned_and_enough_remains() {
+        assert_eq!(chunk_size(CHUNK, 0x1000, 0x2000), CHUNK);
+    }
+
+    #[test]
+    fn chunk_size_falls_back_to_a_byte_when_misaligned() {
+        assert_eq!(chunk_size(CHUNK, 0x1001, 0x2000), 1);
+        assert_eq!(chunk_size(CHUNK, 0x1000, 0x2001), 1);
+    }
+
+    #[test]
+    fn chunk_size_truncates_to_a_byte_for_the_final_remainder() {
+        assert_eq!(chunk_size(CHUNK - 1, 0x1000, 0x2000), 1);
+    }
+}
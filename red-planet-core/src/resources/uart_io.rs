@@ -0,0 +1,308 @@
+//! Host I/O backends for [`Uart0`]: a small trait standing in for "the other end of the wire",
+//! implemented once per host resource a UART can be bound to.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::rc::Weak;
+
+use space_time::allocator::{Allocator, Region};
+
+use crate::board::system_bus::SystemBus;
+use crate::bus::Bus;
+use crate::interrupt::DynIrqCallback;
+
+/// The guest-to-host direction of a UART binding: where transmitted bytes go.
+pub trait UartSink {
+    fn send(&mut self, byte: u8) -> io::Result<()>;
+}
+
+/// The host-to-guest direction of a UART binding: where received bytes come from. Polled by
+/// [`Uart0::poll`] whether or not the UART's RX FIFO has room (a byte received while it is full
+/// is dropped); `Ok(None)` means nothing is available right now, which is not an error.
+pub trait UartSource {
+    fn try_recv(&mut self) -> io::Result<Option<u8>>;
+}
+
+/// Binds a UART to the host's own stdin/stdout.
+#[derive(Debug, Default)]
+pub struct Stdio;
+
+impl UartSink for Stdio {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        io::stdout().write_all(&[byte])
+    }
+}
+
+impl UartSource for Stdio {
+    fn try_recv(&mut self) -> io::Result<Option<u8>> {
+        // Host stdin is blocking by default; a front-end wanting non-blocking RX from a real
+        // terminal should put its stdin handle in non-blocking mode itself (or just use
+        // `InMemory` fed by a dedicated reader thread instead).
+        let mut byte = [0u8; 1];
+        match io::stdin().read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Binds a UART to a TCP socket: bytes the guest transmits are written to the socket, and bytes
+/// available on the socket are delivered to the guest's RX FIFO. The socket is always put into
+/// non-blocking mode so polling it never stalls the emulated machine.
+#[derive(Debug)]
+pub struct Tcp {
+    stream: TcpStream,
+}
+
+impl Tcp {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Tcp { stream })
+    }
+}
+
+impl UartSink for Tcp {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        self.stream.write_all(&[byte])
+    }
+}
+
+impl UartSource for Tcp {
+    fn try_recv(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8; 1];
+        match self.stream.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// An in-memory byte buffer: bytes the guest transmits accumulate in `sent`, and bytes queued in
+/// `to_deliver` are handed to the guest's RX FIFO in order. Doesn't touch the host at all, so it's
+/// the backend to use in tests, and the one a record/replay session falls back to once a
+/// [`crate::board::checkpoint::Replay`] has no more bytes queued for it.
+#[derive(Debug, Default)]
+pub struct InMemory {
+    pub sent: Vec<u8>,
+    pub to_deliver: VecDeque<u8>,
+}
+
+impl UartSink for InMemory {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        self.sent.push(byte);
+        Ok(())
+    }
+}
+
+impl UartSource for InMemory {
+    fn try_recv(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.to_deliver.pop_front())
+    }
+}
+
+/// Offsets (from the device's base address) of the [`Uart0`] registers.
+mod reg {
+    /// Read to pop the oldest byte out of the RX FIFO; reads as 0 if the FIFO is empty, so check
+    /// [`super::status::RX_NOT_EMPTY`] first.
+    pub const RXDATA: u32 = 0x00;
+    /// Write a byte here to transmit it through the bound [`super::UartSink`].
+    pub const TXDATA: u32 = 0x04;
+    /// See [`super::status`].
+    pub const STATUS: u32 = 0x08;
+    /// See [`super::ctrl`].
+    pub const CTRL: u32 = 0x0c;
+    /// Size, in bytes, of the register file.
+    pub const SIZE: u32 = 0x10;
+}
+
+/// Bit layout of the [`Uart0`] status register (`reg::STATUS`).
+mod status {
+    /// Set whenever the RX FIFO has at least one byte available to read.
+    pub const RX_NOT_EMPTY: u32 = 1 << 0;
+}
+
+/// Bit layout of the [`Uart0`] control register (`reg::CTRL`).
+mod ctrl {
+    /// Raise a PLIC interrupt (see [`SystemBus::get_plic_irq_callback`]) whenever the RX FIFO
+    /// becomes non-empty; lowered again once the FIFO is fully drained.
+    pub const RX_IRQ_ENABLE: u32 = 1 << 0;
+}
+
+/// Depth of the [`Uart0`] RX FIFO: how many received bytes can queue up before [`Uart0::poll`]
+/// starts dropping them.
+const RX_FIFO_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    rx_fifo: VecDeque<u8>,
+    ctrl: u32,
+}
+
+/// A UART bound to a pluggable host backend (see [`UartSink`]/[`UartSource`]), with an RX FIFO and
+/// PLIC interrupt generation on received bytes, mirroring the command/status register conventions
+/// used elsewhere in this crate (see e.g. [`crate::resources::dma::Dma`]).
+///
+/// Received bytes aren't pulled continuously: a front-end drives [`Self::poll`] (e.g. once per
+/// scheduler turn) to check the bound source for a new byte and push it into the RX FIFO, raising
+/// the PLIC interrupt if [`ctrl::RX_IRQ_ENABLE`] is set. During a replay, a byte due from
+/// [`SystemBus::poll_uart_rx`] is preferred over the live [`UartSource`], so replay doesn't depend
+/// on the original host resource (stdin, the TCP peer, ...) still existing or still producing the
+/// same bytes; a live byte is recorded via [`SystemBus::record_uart_rx`] as it's delivered.
+///
+/// All registers are word-sized; register with
+/// [`crate::board::system_bus::AccessPolicy::WORD`] (as the PLIC does) so the bus rejects any
+/// other access width before it reaches [`Bus::read`]/[`Bus::write`].
+#[derive(Debug)]
+pub struct Uart0<A: Allocator, Sink, Source> {
+    state: Region<State>,
+    sink: RefCell<Sink>,
+    source: RefCell<Source>,
+    bus: Weak<SystemBus<A>>,
+    irq: DynIrqCallback<A>,
+}
+
+impl<A: Allocator, Sink: UartSink, Source: UartSource> Uart0<A, Sink, Source> {
+    /// Size, in bytes, of the MMIO register file exposed by a [`Uart0`] device. Used when
+    /// registering it with [`SystemBus::register_device`].
+    pub const REGISTER_FILE_SIZE: u32 = reg::SIZE;
+
+    /// Creates a new `Uart0` with an empty RX FIFO, bound to `sink`/`source`, raising RX
+    /// interrupts through `irq`.
+    pub fn new(
+        allocator: &mut A,
+        sink: Sink,
+        source: Source,
+        bus: Weak<SystemBus<A>>,
+        irq: DynIrqCallback<A>,
+    ) -> Self {
+        Uart0 {
+            state: allocator.alloc(State::default()),
+            sink: RefCell::new(sink),
+            source: RefCell::new(source),
+            bus,
+            irq,
+        }
+    }
+
+    /// Checks for a new RX byte, preferring one due from an in-progress replay (see
+    /// [`SystemBus::poll_uart_rx`]) over the bound [`UartSource`], and pushes it into the RX FIFO
+    /// if there's room, raising the PLIC interrupt if [`ctrl::RX_IRQ_ENABLE`] is set. A byte
+    /// arriving once the FIFO is already full is dropped.
+    pub fn poll(&self, allocator: &mut A) {
+        let Some(bus) = self.bus.upgrade() else {
+            return;
+        };
+
+        let byte = if let Some(byte) = bus.poll_uart_rx() {
+            Some(byte)
+        } else if let Ok(Some(byte)) = self.source.borrow_mut().try_recv() {
+            bus.record_uart_rx(byte);
+            Some(byte)
+        } else {
+            None
+        };
+
+        let Some(byte) = byte else {
+            return;
+        };
+
+        let state = allocator.get_mut(self.state);
+        if state.rx_fifo.len() >= RX_FIFO_DEPTH {
+            return;
+        }
+        state.rx_fifo.push_back(byte);
+        if state.ctrl & ctrl::RX_IRQ_ENABLE != 0 {
+            self.irq.raise(allocator);
+        }
+    }
+}
+
+impl<A: Allocator, Sink: UartSink, Source: UartSource> Bus<A> for Uart0<A, Sink, Source> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+        let value = match address {
+            reg::RXDATA => {
+                let state = allocator.get_mut(self.state);
+                let byte = state.rx_fifo.pop_front().unwrap_or(0);
+                if state.rx_fifo.is_empty() {
+                    self.irq.lower(allocator);
+                }
+                byte as u32
+            }
+            reg::STATUS => status_value(allocator.get(self.state)),
+            reg::CTRL => allocator.get(self.state).ctrl,
+            _ => return,
+        };
+        buf.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+        let state = allocator.get(self.state);
+        let value = match address {
+            reg::RXDATA => state.rx_fifo.front().copied().unwrap_or(0) as u32,
+            reg::STATUS => status_value(state),
+            reg::CTRL => state.ctrl,
+            _ => return,
+        };
+        buf.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+        let value = u32::from_le_bytes(buf.try_into().unwrap());
+        match address {
+            reg::TXDATA => {
+                // Best-effort: a host-side send failure (e.g. the TCP peer went away) has no
+                // guest-visible error signal on a UART this simple, and is not fatal to the
+                // emulated machine.
+                let _ = self.sink.borrow_mut().send(value as u8);
+            }
+            reg::CTRL => allocator.get_mut(self.state).ctrl = value,
+            _ => {}
+        }
+    }
+}
+
+fn status_value(state: &State) -> u32 {
+    if state.rx_fifo.is_empty() {
+        0
+    } else {
+        status::RX_NOT_EMPTY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `Uart0` itself is generic over `Allocator`, which this crate fragment doesn't vendor a
+    // usable implementation of, so `Bus::read`/`write`/`poll` can't be exercised end-to-end here;
+    // `status_value` is the one piece of its logic with no such dependency.
+
+    #[test]
+    fn status_is_not_empty_once_a_byte_is_queued() {
+        let mut state = State::default();
+        assert_eq!(status_value(&state), 0);
+
+        state.rx_fifo.push_back(b'x');
+        assert_eq!(status_value(&state), status::RX_NOT_EMPTY);
+    }
+
+    #[test]
+    fn in_memory_backend_echoes_sent_bytes_and_drains_queued_ones_in_order() {
+        let mut backend = InMemory::default();
+        backend.send(1).unwrap();
+        backend.send(2).unwrap();
+        assert_eq!(backend.sent, vec![1, 2]);
+
+        backend.to_deliver.push_back(b'a');
+        backend.to_deliver.push_back(b'b');
+        assert_eq!(backend.try_recv().unwrap(), Some(b'a'));
+        assert_eq!(backend.try_recv().unwrap(), Some(b'b'));
+        assert_eq!(backend.try_recv().unwrap(), None);
+    }
+}